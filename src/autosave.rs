@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::log;
+use crate::session::Session;
+
+/// Periodic snapshots of the drawing to a recovery file
+///
+/// Snapshots are written by a background thread so saving never blocks the
+/// event loop, and at most once per interval.
+pub struct Autosave {
+    path: PathBuf,
+    sender: Option<Sender<Session>>,
+    thread: Option<JoinHandle<()>>, // Only thread writing the recovery file
+    interval: Duration,
+    last_save: Instant,
+    has_changes: bool,
+}
+
+impl Autosave {
+    /// Autosave to `path` every `interval`, disabled if the interval is zero
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        let (sender, thread) = if interval > Duration::from_secs(0) {
+            let (sender, receiver) = mpsc::channel::<Session>();
+            let thread_path = path.clone();
+            let thread = thread::spawn(move || {
+                for session in receiver {
+                    write_recovery_file(&session, &thread_path);
+                }
+            });
+            (Some(sender), Some(thread))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            path,
+            sender,
+            thread,
+            interval,
+            last_save: Instant::now(),
+            has_changes: false,
+        }
+    }
+
+    /// The drawing changed since the last snapshot
+    pub fn mark_changed(&mut self) {
+        self.has_changes = true;
+    }
+
+    /// Time at which the next snapshot is due, if there is anything to save
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.has_changes && self.sender.is_some() {
+            Some(self.last_save + self.interval)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        match self.deadline() {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /// Send a snapshot to the background thread
+    pub fn save(&mut self, session: Session) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(session);
+        }
+        self.last_save = Instant::now();
+        self.has_changes = false;
+    }
+
+    /// Write a last snapshot before quitting, without waiting for the interval
+    ///
+    /// The snapshot goes through the background thread after the pending
+    /// ones, and the thread is stopped once they are all written.
+    pub fn save_now(&mut self, session: Session) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(session);
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error(format_args!(
+                    "Autosave thread panicked, {} may be outdated",
+                    self.path.display()
+                ));
+            }
+        }
+        self.has_changes = false;
+    }
+}
+
+/// An empty drawing has nothing to recover, the file is removed instead
+fn write_recovery_file(session: &Session, path: &Path) {
+    let result = if session.strokes.is_empty() {
+        match fs::remove_file(path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    } else {
+        session.save(path)
+    };
+
+    if let Err(error) = result {
+        log::error(format_args!(
+            "Failed to autosave to {}: {}",
+            path.display(),
+            error
+        ));
+    }
+}
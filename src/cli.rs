@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use clap::{App, Arg};
+
+/// Launch options given on the command line
+pub struct Options {
+    pub config_path: Option<PathBuf>,
+    pub session_path: Option<PathBuf>,
+    pub color_index: Option<usize>,
+    pub brush_size: Option<f32>,
+    pub monitors: Option<Vec<usize>>,
+    pub is_background_visible: bool,
+    pub export_path: Option<PathBuf>,
+}
+
+pub fn parse_options() -> Options {
+    let matches = App::new("Inke")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Screen drawing utility")
+        .arg(
+            Arg::with_name("session")
+                .value_name("SESSION")
+                .help("Session file to open, also used by the save session shortcut"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Config file to use, created with the default values if missing"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("INDEX")
+                .validator(|value| validate_number::<usize>(&value))
+                .help("Starting brush color, as an index in brush_colors"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .value_name("PIXELS")
+                .validator(|value| validate_brush_size(&value))
+                .help("Starting brush size"),
+        )
+        .arg(
+            Arg::with_name("monitor")
+                .short("m")
+                .long("monitor")
+                .value_name("INDEX")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|value| validate_number::<usize>(&value))
+                .help("Monitor to cover, can be repeated. All monitors are covered by default"),
+        )
+        .arg(
+            Arg::with_name("background")
+                .short("b")
+                .long("background")
+                .help("Start with the background visible"),
+        )
+        .arg(
+            Arg::with_name("export")
+                .long("export")
+                .value_name("OUTPUT")
+                .requires("session")
+                .help("Render SESSION to a .png or .svg file and exit without opening a window"),
+        )
+        .get_matches();
+
+    Options {
+        config_path: matches.value_of("config").map(PathBuf::from),
+        session_path: matches.value_of("session").map(PathBuf::from),
+        color_index: matches.value_of("color").and_then(|v| v.parse().ok()),
+        brush_size: matches.value_of("size").and_then(|v| v.parse().ok()),
+        monitors: matches
+            .values_of("monitor")
+            .map(|values| values.filter_map(|v| v.parse().ok()).collect()),
+        is_background_visible: matches.is_present("background"),
+        export_path: matches.value_of("export").map(PathBuf::from),
+    }
+}
+
+fn validate_number<T: std::str::FromStr>(value: &str) -> Result<(), String> {
+    value
+        .parse::<T>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' is not a valid number", value))
+}
+
+/// Same range as `default_brush_size` in the config
+fn validate_brush_size(value: &str) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(size) if size > 0.0 && size.is_finite() => Ok(()),
+        Ok(_) => Err(format!("'{}' is not a positive size", value)),
+        Err(_) => Err(format!("'{}' is not a valid number", value)),
+    }
+}
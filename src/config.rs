@@ -0,0 +1,716 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fmt, fs, io, thread};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::keybindings::{self, parse_key_combo, Action, KeyCombo};
+use crate::log;
+use crate::palette::{self, BrushColor, BrushSize};
+use crate::shape::{ArrowHead, ShapeKind};
+use crate::stroke::PressureCurve;
+use crate::tessellation::LineJoin;
+
+/// Version of the config file format, increased when settings change meaning
+///
+/// 2: `brush_colors` and `brush_sizes` became lists of presets, `keybindings` added
+/// 3: `pressure_curve` added, with the `toggle_pressure_preview` action
+/// 4: `highlighter_opacity` added, with the `toggle_highlighter` action
+/// 5: `laser_fade_duration` added, with the `toggle_laser` action
+/// 6: `auto_fade_delay` added, with the `toggle_auto_fade` action
+/// 7: `arrow_head` added, with the `shape` actions
+/// 8: `export_dir` added
+pub const CONFIG_VERSION: u8 = 8;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub config_version: u8,
+    pub smoothing_range: usize,
+    pub smoothing_intensity: usize,
+    pub default_brush_size: f32,
+    pub default_brush_color_index: i32,
+    pub brush_colors: Vec<BrushColor>,
+    pub brush_sizes: Vec<BrushSize>,
+    pub pressure_curve: PressureCurve,
+    pub line_join: LineJoin,
+    pub highlighter_opacity: f32,
+    pub laser_fade_duration: u64,
+    pub auto_fade_delay: u64,
+    pub arrow_head: ArrowHead,
+    pub background_color: [u32; 3],
+    pub background_color_opacity: f32,
+    pub undo_history_size: usize,
+    pub save_session_on_exit: bool,
+    pub autosave_interval: u64,
+    pub export_dir: Option<PathBuf>,
+    pub keybindings: BTreeMap<String, Action>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_version: CONFIG_VERSION,
+            smoothing_range: 1,
+            smoothing_intensity: 1,
+            default_brush_size: 3.0,
+            default_brush_color_index: 0,
+            brush_colors: palette::default_palette(),
+            brush_sizes: palette::default_brush_sizes(),
+            pressure_curve: PressureCurve::default(), // Used by the sizes without their own
+            line_join: LineJoin::Round,
+            highlighter_opacity: 0.4,
+            laser_fade_duration: 1500, // milliseconds
+            auto_fade_delay: 5,        // seconds
+            arrow_head: ArrowHead::default(),
+            background_color: [0, 0, 0],
+            background_color_opacity: 0.8,
+            undo_history_size: 0, // 0 for unlimited
+            save_session_on_exit: false,
+            autosave_interval: 10,            // seconds, 0 to disable
+            export_dir: default_export_dir(), // None for next to the config file
+            keybindings: keybindings::default_keybindings(),
+        }
+    }
+}
+
+/// Why the config file can't be used as it is
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file can't be read or written
+    Io(io::Error),
+    /// The file is not valid json
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A setting has the wrong type or format
+    Field {
+        field: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A setting is out of range, a valid value is used instead
+    Range { field: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "{}", error),
+            ConfigError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "line {} column {}: {}", line, column, message),
+            ConfigError::Field {
+                field,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "line {} column {}, `{}`: {}",
+                line, column, field, message
+            ),
+            ConfigError::Range { field, message } => write!(f, "`{}`: {}", field, message),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+const CONFIG_FILE: &str = "config.json";
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Config file to use
+///
+/// In order: the file given on the command line, `config.json` next to the
+/// executable, then `inke/config.json` in the user config directory, where
+/// it is created if there is none. The path is absolute, the files written
+/// next to the config don't depend on the current directory.
+pub fn find_config_file(cli_path: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = cli_path {
+        return absolute_path(path);
+    }
+
+    let executable_path = env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.parent()?.join(CONFIG_FILE)));
+    let user_path = user_config_dir().map(|dir| dir.join("inke").join(CONFIG_FILE));
+
+    if let Some(path) = [&executable_path, &user_path]
+        .iter()
+        .filter_map(|path| path.as_ref())
+        .find(|path| path.exists())
+    {
+        return path.clone();
+    }
+
+    absolute_path(
+        user_path
+            .or(executable_path)
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE)),
+    )
+}
+
+/// Relative paths are resolved from the current directory
+fn absolute_path(path: PathBuf) -> PathBuf {
+    if path.is_absolute() {
+        return path;
+    }
+    match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path,
+    }
+}
+
+/// `Pictures/inke` in the home directory, where exports are easy to find
+fn default_export_dir() -> Option<PathBuf> {
+    let home_var = if cfg!(target_os = "windows") {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    };
+    let home = PathBuf::from(env::var_os(home_var)?);
+    Some(home.join("Pictures").join("inke"))
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config`
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn user_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".config")))
+}
+
+/// `~/Library/Application Support`
+#[cfg(target_os = "macos")]
+fn user_config_dir() -> Option<PathBuf> {
+    Some(PathBuf::from(env::var_os("HOME")?).join("Library/Application Support"))
+}
+
+/// `%APPDATA%`
+#[cfg(target_os = "windows")]
+fn user_config_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+fn create_default_config_file(path: &Path) -> io::Result<String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let default_config_json = serde_json::to_string_pretty(&Config::default())?;
+    fs::write(path, &default_config_json)?;
+    Ok(default_config_json)
+}
+
+/// Read the config file, created with the default values if missing
+///
+/// Configs written by an older version, or missing some settings, are
+/// migrated and written back in the current format. The original file is
+/// kept next to it, such as `config.v1.bak`.
+///
+/// Errors are logged. A config that can't be read falls back to the
+/// default values, and settings out of range to valid values.
+pub fn load_config(path: &Path) -> Config {
+    if !path.exists() {
+        if let Err(error) = create_default_config_file(path) {
+            log::error(format_args!(
+                "Failed to create default config file {}: {}",
+                path.display(),
+                error
+            ));
+            return Config::default();
+        }
+    }
+
+    match read_config(path, true) {
+        Ok(config) => validated_config(path, config),
+        Err(error) => {
+            log::error(format_args!(
+                "Invalid config {}, using the default values: {}",
+                path.display(),
+                error
+            ));
+            Config::default()
+        }
+    }
+}
+
+/// Read the config file again after it changed
+///
+/// Unlike `load_config`, the file is never written and there is no config
+/// if it can't be read, so the current one can be kept.
+pub fn reload_config(path: &Path) -> Option<Config> {
+    match read_config(path, false) {
+        Ok(config) => {
+            log::info(format_args!("Reloaded config file {}", path.display()));
+            Some(validated_config(path, config))
+        }
+        Err(error) => {
+            log::error(format_args!(
+                "Invalid config {}, keeping the current one: {}",
+                path.display(),
+                error
+            ));
+            None
+        }
+    }
+}
+
+/// Call `on_change` from a background thread when the config file is modified
+///
+/// The modification time is polled since editors often replace the file
+/// instead of writing to it.
+pub fn watch_config_file(path: PathBuf, on_change: impl Fn() + Send + 'static) {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+        loop {
+            thread::sleep(CONFIG_POLL_INTERVAL);
+            let current_modified = modified(&path);
+            // A removed file keeps the current config
+            if current_modified != last_modified && current_modified.is_some() {
+                on_change();
+            }
+            last_modified = current_modified;
+        }
+    });
+}
+
+fn validated_config(path: &Path, mut config: Config) -> Config {
+    for error in validate_config(&mut config) {
+        log::error(format_args!("Invalid config {}: {}", path.display(), error));
+    }
+    config
+}
+
+/// Parse the config file, migrated to the current version
+///
+/// With `can_rewrite`, an outdated file is also updated.
+fn read_config(path: &Path, can_rewrite: bool) -> Result<Config, ConfigError> {
+    let config_file_contents = fs::read_to_string(path)?;
+
+    let mut value: Value =
+        serde_json::from_str(&config_file_contents).map_err(|error| ConfigError::Syntax {
+            line: error.line(),
+            column: error.column(),
+            message: error_message(&error),
+        })?;
+    // The first version always wrote its version number
+    let version = value
+        .get("config_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1) as u8;
+
+    if version > CONFIG_VERSION {
+        log::error(format_args!(
+            "Config version {} is newer than the supported version {}, unknown settings are ignored",
+            version, CONFIG_VERSION
+        ));
+    } else {
+        migrate_config(&mut value, version);
+    }
+
+    let mut config: Config = serde_json::from_value(value.clone())
+        .map_err(|error| field_error(&config_file_contents, &value, &error))?;
+
+    // A newer config is left as is for the version that wrote it
+    let is_outdated = version < CONFIG_VERSION
+        || (version == CONFIG_VERSION && has_missing_settings(&value, &config));
+    if is_outdated && can_rewrite {
+        config.config_version = CONFIG_VERSION;
+        if let Err(error) = rewrite_config_file(path, &config, version) {
+            log::error(format_args!(
+                "Failed to update {}: {}",
+                path.display(),
+                error
+            ));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Error of a setting with the wrong type or format
+///
+/// The values don't keep their position in the file, so the position comes
+/// from deserializing the file again, and the field from deserializing the
+/// settings one by one.
+fn field_error(contents: &str, value: &Value, error: &serde_json::Error) -> ConfigError {
+    let (line, column) = match serde_json::from_str::<Config>(contents) {
+        Err(error) => (error.line(), error.column()),
+        Ok(_) => (0, 0),
+    };
+    let field = match value {
+        Value::Object(settings) => settings
+            .iter()
+            .find(|(key, setting)| {
+                let mut single_setting = Map::new();
+                single_setting.insert(key.to_string(), (*setting).clone());
+                serde_json::from_value::<Config>(Value::Object(single_setting)).is_err()
+            })
+            .map(|(key, _)| key.clone()),
+        _ => None,
+    };
+
+    ConfigError::Field {
+        field: field.unwrap_or_default(),
+        line,
+        column,
+        message: error_message(error),
+    }
+}
+
+/// Message of a json error, without its position
+fn error_message(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    let position = format!(" at line {} column {}", error.line(), error.column());
+    message
+        .strip_suffix(&position)
+        .unwrap_or(&message)
+        .to_string()
+}
+
+/// Replace settings out of range by valid values
+fn validate_config(config: &mut Config) -> Vec<ConfigError> {
+    let defaults = Config::default();
+    let mut errors = Vec::new();
+    let mut range_error = |field: &str, message: String| {
+        errors.push(ConfigError::Range {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    if config.brush_colors.is_empty() {
+        range_error(
+            "brush_colors",
+            "no colors, using the default palette".to_string(),
+        );
+        config.brush_colors = defaults.brush_colors;
+    }
+    for (i, brush_color) in config.brush_colors.iter_mut().enumerate() {
+        if brush_color.color.0.iter().any(|channel| *channel > 255) {
+            range_error(
+                &format!("brush_colors[{}]", i),
+                "channels are limited to 255".to_string(),
+            );
+            brush_color.color.0 = brush_color.color.0.map(|channel| channel.min(255));
+        }
+    }
+
+    let n_sizes = config.brush_sizes.len();
+    config.brush_sizes.retain(|preset| preset.size > 0.0);
+    if config.brush_sizes.len() != n_sizes {
+        range_error(
+            "brush_sizes",
+            "sizes must be positive, removed the others".to_string(),
+        );
+    }
+    if config.brush_sizes.is_empty() {
+        range_error(
+            "brush_sizes",
+            "no sizes, using the default presets".to_string(),
+        );
+        config.brush_sizes = defaults.brush_sizes;
+    }
+    if let Err(message) = config.pressure_curve.validate() {
+        range_error(
+            "pressure_curve",
+            format!("{}, using a linear curve", message),
+        );
+        config.pressure_curve = PressureCurve::default();
+    }
+    for (i, preset) in config.brush_sizes.iter_mut().enumerate() {
+        if let Some(Err(message)) = preset.pressure_curve.as_ref().map(PressureCurve::validate) {
+            range_error(
+                &format!("brush_sizes[{}].pressure_curve", i),
+                format!("{}, using the pressure_curve of the config", message),
+            );
+            preset.pressure_curve = None;
+        }
+    }
+
+    if config.default_brush_color_index < 0
+        || config.default_brush_color_index as usize >= config.brush_colors.len()
+    {
+        range_error(
+            "default_brush_color_index",
+            format!(
+                "{} is not the index of a brush color, using 0",
+                config.default_brush_color_index
+            ),
+        );
+        config.default_brush_color_index = 0;
+    }
+    if config.default_brush_size <= 0.0 {
+        range_error(
+            "default_brush_size",
+            format!(
+                "{} is not positive, using {}",
+                config.default_brush_size, defaults.default_brush_size
+            ),
+        );
+        config.default_brush_size = defaults.default_brush_size;
+    }
+    if config.background_color.iter().any(|channel| *channel > 255) {
+        range_error(
+            "background_color",
+            "channels are limited to 255".to_string(),
+        );
+        config.background_color = config.background_color.map(|channel| channel.min(255));
+    }
+    if !(0.0..=1.0).contains(&config.background_color_opacity) {
+        range_error(
+            "background_color_opacity",
+            format!("{} is not between 0 and 1", config.background_color_opacity),
+        );
+        config.background_color_opacity = config.background_color_opacity.clamp(0.0, 1.0);
+    }
+    if config.highlighter_opacity <= 0.0 || config.highlighter_opacity > 1.0 {
+        range_error(
+            "highlighter_opacity",
+            format!("{} is not above 0 and up to 1", config.highlighter_opacity),
+        );
+        config.highlighter_opacity = Config::default().highlighter_opacity;
+    }
+    if let Err(message) = config.arrow_head.validate() {
+        range_error("arrow_head", format!("{}, using the default head", message));
+        config.arrow_head = ArrowHead::default();
+    }
+
+    errors
+}
+
+/// Bring a config of an older version to the current format
+///
+/// Settings added by a version don't need a step, they get their default
+/// value when missing.
+fn migrate_config(value: &mut Value, version: u8) {
+    if version < 2 {
+        // The fixed palette had no names, the stock colors get theirs
+        let stock_colors = palette::default_palette();
+        if let Some(Value::Array(colors)) = value.get_mut("brush_colors") {
+            for color in colors.iter_mut() {
+                let rgb = serde_json::from_value::<[u32; 3]>(color.clone()).ok();
+                let stock_color = stock_colors
+                    .iter()
+                    .find(|stock_color| Some(stock_color.color.0) == rgb);
+                if let Some(Ok(named_color)) = stock_color.map(serde_json::to_value) {
+                    *color = named_color;
+                }
+            }
+        }
+    }
+    if version < 3 {
+        add_default_keybindings(value, &[Action::TogglePressurePreview]);
+    }
+    if version < 4 {
+        add_default_keybindings(value, &[Action::ToggleHighlighter]);
+    }
+    if version < 5 {
+        add_default_keybindings(value, &[Action::ToggleLaser]);
+    }
+    if version < 6 {
+        add_default_keybindings(value, &[Action::ToggleAutoFade]);
+    }
+    if version < 7 {
+        add_default_keybindings(
+            value,
+            &[
+                Action::Shape(ShapeKind::Line),
+                Action::Shape(ShapeKind::Arrow),
+                Action::Shape(ShapeKind::Rectangle),
+                Action::Shape(ShapeKind::Ellipse),
+            ],
+        );
+    }
+}
+
+/// Bind new actions to their default keys, unless these keys are taken
+///
+/// Keys are compared as combos, `P` or `shift+ctrl+a` take `p` and `ctrl+shift+a`.
+fn add_default_keybindings(value: &mut Value, actions: &[Action]) {
+    if let Some(Value::Object(bindings)) = value.get_mut("keybindings") {
+        let mut taken_combos: Vec<KeyCombo> = bindings
+            .keys()
+            .filter_map(|keys| parse_key_combo(keys).ok())
+            .collect();
+        for (keys, action) in keybindings::default_keybindings() {
+            let combo = match parse_key_combo(&keys) {
+                Ok(combo) => combo,
+                Err(_) => continue,
+            };
+            if actions.contains(&action) && !taken_combos.contains(&combo) {
+                if let Ok(action) = serde_json::to_value(action) {
+                    bindings.insert(keys, action);
+                    taken_combos.push(combo);
+                }
+            }
+        }
+    }
+}
+
+/// Settings of the current version absent from the file
+fn has_missing_settings(value: &Value, config: &Config) -> bool {
+    match (value, serde_json::to_value(config)) {
+        (Value::Object(file), Ok(Value::Object(current))) => {
+            current.keys().any(|key| !file.contains_key(key))
+        }
+        _ => false,
+    }
+}
+
+/// Write the config in the current format, keeping a backup of the original
+fn rewrite_config_file(path: &Path, config: &Config, version: u8) -> std::io::Result<()> {
+    let backup_path = path.with_extension(format!("v{}.bak", version));
+    fs::copy(path, &backup_path)?;
+
+    let config_json = serde_json::to_string_pretty(config)?;
+    fs::write(path, config_json)?;
+
+    log::info(format_args!(
+        "Updated {} to version {}, the original is kept as {}",
+        path.display(),
+        CONFIG_VERSION,
+        backup_path.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::Keybindings;
+
+    /// Config file with these contents, in its own temporary directory
+    fn config_file(test_name: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("inke-{}-{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const V1_CONFIG: &str = r#"{
+        "config_version": 1,
+        "smoothing_range": 2,
+        "smoothing_intensity": 1,
+        "default_brush_size": 4.0,
+        "default_brush_color_index": 1,
+        "brush_colors": [
+            [255, 255, 255], [10, 10, 10], [255, 150, 0], [255, 0, 220],
+            [255, 50, 50], [25, 255, 75], [25, 75, 255], [1, 2, 3]
+        ],
+        "brush_sizes": [1.0, 2.0, 4.0, 8.0, 16.0],
+        "background_color": [0, 0, 0],
+        "background_color_opacity": 0.5
+    }"#;
+
+    #[test]
+    fn v1_config_is_migrated() {
+        let path = config_file("v1", V1_CONFIG);
+        let config = load_config(&path);
+
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.smoothing_range, 2);
+        assert_eq!(config.default_brush_size, 4.0);
+        // Stock colors get their name, the others stay bare
+        assert_eq!(config.brush_colors[0].name.as_deref(), Some("white"));
+        assert_eq!(config.brush_colors[7].name, None);
+        assert_eq!(config.brush_colors[7].color.0, [1, 2, 3]);
+        let sizes: Vec<f32> = config.brush_sizes.iter().map(|size| size.size).collect();
+        assert_eq!(sizes, [1.0, 2.0, 4.0, 8.0, 16.0]);
+        assert_eq!(config.keybindings, keybindings::default_keybindings());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn outdated_config_is_rewritten_with_a_backup() {
+        let path = config_file("rewrite", V1_CONFIG);
+        let config = load_config(&path);
+
+        let backup = fs::read_to_string(path.with_file_name("config.v1.bak")).unwrap();
+        assert_eq!(backup, V1_CONFIG);
+        let rewritten: Config = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&rewritten).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        // Up to date now, read as is
+        let reloaded = reload_config(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn current_config_missing_settings_is_rewritten() {
+        let contents = format!(r#"{{ "config_version": {} }}"#, CONFIG_VERSION);
+        let path = config_file("missing", &contents);
+        load_config(&path);
+
+        let backup_name = format!("config.v{}.bak", CONFIG_VERSION);
+        assert_eq!(
+            fs::read_to_string(path.with_file_name(backup_name)).unwrap(),
+            contents
+        );
+        let rewritten: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!has_missing_settings(&rewritten, &Config::default()));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn newer_config_is_left_untouched() {
+        let contents = format!(
+            r#"{{ "config_version": {}, "default_brush_size": 5.0, "future_setting": true }}"#,
+            CONFIG_VERSION + 1
+        );
+        let path = config_file("newer", &contents);
+        let config = load_config(&path);
+
+        assert_eq!(config.default_brush_size, 5.0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        let files = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(files, 1);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn default_keys_taken_by_another_spelling_are_not_added() {
+        let mut value: Value = serde_json::from_str(
+            r#"{ "config_version": 2, "keybindings": { "P": "toggle_background" } }"#,
+        )
+        .unwrap();
+        migrate_config(&mut value, 2);
+
+        let bindings = value["keybindings"].as_object().unwrap();
+        assert!(!bindings.contains_key("p"));
+        // Free default keys are still bound
+        assert_eq!(bindings["g"], "toggle_highlighter");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        let (_, errors) = Keybindings::new(&config.keybindings);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
@@ -0,0 +1,510 @@
+use std::mem;
+
+use crate::history::Change;
+use crate::stroke::{Sample, Stroke};
+
+/// Strokes removed and added during a single eraser drag
+#[derive(Default, Debug)]
+pub struct Eraser {
+    removed: Vec<Stroke>,
+    added: Vec<Stroke>,
+    last_position: Option<(f32, f32)>,
+}
+
+impl Eraser {
+    /// Remove every stroke touched by the eraser moving to (x, y)
+    ///
+    /// The path from the previous position is tested as well so fast drags
+    /// don't skip over strokes. Returns true if any stroke was removed.
+    pub fn erase_strokes(
+        &mut self,
+        strokes: &mut Vec<Stroke>,
+        x: f32,
+        y: f32,
+        radius: f32,
+    ) -> bool {
+        let (from, to) = self.move_to(x, y);
+
+        let (touched, kept): (Vec<Stroke>, Vec<Stroke>) = mem::take(strokes)
+            .into_iter()
+            .partition(|stroke| stroke_distance(stroke, from, to) <= radius);
+        *strokes = kept;
+
+        let has_erased = !touched.is_empty();
+        for stroke in touched {
+            self.remove(stroke);
+        }
+        has_erased
+    }
+
+    /// Cut the parts of the strokes under the eraser moving to (x, y)
+    ///
+    /// Strokes are cut where they are drawn, after smoothing. The pieces keep
+    /// the smoothed samples and are not smoothed again, so the ink left is
+    /// exactly what was drawn. They get new ids but keep the rest of their
+    /// original style, expiry and place in the drawing order. Returns true
+    /// if any stroke was cut.
+    pub fn cut_strokes(
+        &mut self,
+        strokes: &mut Vec<Stroke>,
+        x: f32,
+        y: f32,
+        radius: f32,
+        next_stroke_id: &mut u64,
+    ) -> bool {
+        let (from, to) = self.move_to(x, y);
+
+        // Follow the eraser path in steps small enough to not leave any ink behind
+        let path_length = point_distance(from, to);
+        let n_steps = (path_length / (radius * 0.5).max(0.5)).ceil().max(1.0) as usize;
+        let circles: Vec<(f32, f32)> = (0..=n_steps)
+            .map(|i| {
+                let t = i as f32 / n_steps as f32;
+                (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+            })
+            .collect();
+
+        // Strokes out of the box around the eraser path can't be cut
+        let path_bounds = (
+            (from.0.min(to.0) - radius, from.1.min(to.1) - radius),
+            (from.0.max(to.0) + radius, from.1.max(to.1) + radius),
+        );
+
+        let mut has_cut = false;
+        for stroke in mem::take(strokes) {
+            if !boxes_overlap(stroke_bounds(&stroke), path_bounds) {
+                strokes.push(stroke);
+                continue;
+            }
+
+            let samples = stroke.smoothed_samples();
+            let mut stroke_pieces = vec![samples.clone()];
+            for &center in &circles {
+                stroke_pieces = stroke_pieces
+                    .iter()
+                    .flat_map(|samples| cut_samples(samples, &stroke, center, radius))
+                    .collect();
+            }
+
+            if stroke_pieces.len() == 1 && stroke_pieces[0] == samples {
+                strokes.push(stroke);
+                continue;
+            }
+
+            has_cut = true;
+            let mut style = stroke.style.clone();
+            style.smoothing_range = 0;
+            for samples in stroke_pieces {
+                let mut piece = Stroke::new(*next_stroke_id, style.clone());
+                piece.order = stroke.order;
+                piece.samples = samples;
+                piece.expires_at = stroke.expires_at;
+                *next_stroke_id += 1;
+                self.added.push(piece.clone());
+                strokes.push(piece);
+            }
+            self.remove(stroke);
+        }
+        has_cut
+    }
+
+    /// End the drag, returning the change to record as a single undo step
+    pub fn finish(&mut self) -> Option<Change> {
+        self.last_position = None;
+        if self.removed.is_empty() && self.added.is_empty() {
+            return None;
+        }
+        Some(Change {
+            removed: mem::take(&mut self.removed),
+            added: mem::take(&mut self.added),
+        })
+    }
+
+    /// Returns the segment travelled by the eraser since the last call
+    fn move_to(&mut self, x: f32, y: f32) -> ((f32, f32), (f32, f32)) {
+        let from = self.last_position.unwrap_or((x, y));
+        self.last_position = Some((x, y));
+        (from, (x, y))
+    }
+
+    /// Pieces created earlier in the same drag never existed for the history
+    fn remove(&mut self, stroke: Stroke) {
+        let n_added = self.added.len();
+        self.added.retain(|added| added.id != stroke.id);
+        if self.added.len() == n_added {
+            self.removed.push(stroke);
+        }
+    }
+}
+
+/// Split a list of samples around the parts within the eraser circle
+///
+/// The circle is grown by the stroke width so no ink is left under the eraser.
+fn cut_samples(
+    samples: &[Sample],
+    stroke: &Stroke,
+    center: (f32, f32),
+    radius: f32,
+) -> Vec<Vec<Sample>> {
+    let cut_radius = |sample: &Sample| radius + stroke.style.half_width(sample.pressure);
+
+    let first = match samples.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    if samples.len() == 1 {
+        let is_erased = point_distance((first.x, first.y), center) <= cut_radius(first);
+        return if is_erased {
+            Vec::new()
+        } else {
+            vec![samples.to_vec()]
+        };
+    }
+
+    let mut pieces = Vec::new();
+    let mut piece = if point_distance((first.x, first.y), center) <= cut_radius(first) {
+        None
+    } else {
+        Some(vec![*first])
+    };
+
+    for pair in samples.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let segment_radius = cut_radius(a).max(cut_radius(b));
+
+        match segment_circle_intersection((a.x, a.y), (b.x, b.y), center, segment_radius) {
+            Some((t_in, t_out)) => {
+                if let Some(mut current) = piece.take() {
+                    if t_in > 0.0 {
+                        current.push(lerp_sample(a, b, t_in));
+                    }
+                    pieces.push(current);
+                }
+                if t_out < 1.0 {
+                    piece = Some(vec![lerp_sample(a, b, t_out), *b]);
+                }
+            }
+            None => piece.get_or_insert_with(|| vec![*a]).push(*b),
+        }
+    }
+    pieces.extend(piece);
+
+    // A single sample left at the edge of the circle would only draw a dot
+    pieces.retain(|piece| piece.len() > 1);
+    pieces
+}
+
+/// Part of the segment a-b inside the circle, as a range of 0..1 positions along the segment
+fn segment_circle_intersection(
+    a: (f32, f32),
+    b: (f32, f32),
+    center: (f32, f32),
+    radius: f32,
+) -> Option<(f32, f32)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (fx, fy) = (a.0 - center.0, a.1 - center.1);
+
+    let qa = dx * dx + dy * dy;
+    let qb = 2.0 * (fx * dx + fy * dy);
+    let qc = fx * fx + fy * fy - radius * radius;
+
+    if qa == 0.0 {
+        return if qc <= 0.0 { Some((0.0, 1.0)) } else { None };
+    }
+
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    let t_in = ((-qb - discriminant.sqrt()) / (2.0 * qa)).max(0.0);
+    let t_out = ((-qb + discriminant.sqrt()) / (2.0 * qa)).min(1.0);
+    if t_in >= t_out {
+        return None;
+    }
+    Some((t_in, t_out))
+}
+
+fn lerp_sample(a: &Sample, b: &Sample, t: f32) -> Sample {
+    Sample {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+        pressure: a.pressure + (b.pressure - a.pressure) * t,
+        time_ms: a.time_ms + ((b.time_ms.saturating_sub(a.time_ms)) as f32 * t) as u32,
+    }
+}
+
+/// Top left and bottom right corners of the box around the ink of a stroke
+///
+/// Smoothing only moves samples towards their neighbours, the box around the
+/// raw samples also holds the drawn ones.
+fn stroke_bounds(stroke: &Stroke) -> ((f32, f32), (f32, f32)) {
+    stroke.samples.iter().fold(
+        ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+        |(min, max), sample| {
+            let half_width = stroke.style.half_width(sample.pressure);
+            (
+                (
+                    min.0.min(sample.x - half_width),
+                    min.1.min(sample.y - half_width),
+                ),
+                (
+                    max.0.max(sample.x + half_width),
+                    max.1.max(sample.y + half_width),
+                ),
+            )
+        },
+    )
+}
+
+fn boxes_overlap(a: ((f32, f32), (f32, f32)), b: ((f32, f32), (f32, f32))) -> bool {
+    let ((a_min, a_max), (b_min, b_max)) = (a, b);
+    a_min.0 <= b_max.0 && b_min.0 <= a_max.0 && a_min.1 <= b_max.1 && b_min.1 <= a_max.1
+}
+
+fn point_distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1)).sqrt()
+}
+
+/// Distance in pixels between the edge of a stroke, as drawn, and the segment a-b
+fn stroke_distance(stroke: &Stroke, a: (f32, f32), b: (f32, f32)) -> f32 {
+    let samples = stroke.smoothed_samples();
+    let mut min_distance = f32::INFINITY;
+
+    for (i, sample) in samples.iter().enumerate() {
+        let prev = &samples[i.saturating_sub(1)];
+        let half_width = stroke.style.half_width(sample.pressure.max(prev.pressure));
+        let distance = segment_distance((prev.x, prev.y), (sample.x, sample.y), a, b) - half_width;
+        min_distance = min_distance.min(distance);
+    }
+
+    min_distance
+}
+
+/// Shortest distance between the point p and the segment a-b
+fn point_segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = abx * abx + aby * aby;
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * abx + (p.1 - a.1) * aby) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (dx, dy) = (a.0 + abx * t - p.0, a.1 + aby * t - p.1);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Shortest distance between the segments a1-a2 and b1-b2
+fn segment_distance(a1: (f32, f32), a2: (f32, f32), b1: (f32, f32), b2: (f32, f32)) -> f32 {
+    if segments_intersect(a1, a2, b1, b2) {
+        return 0.0;
+    }
+    point_segment_distance(a1, b1, b2)
+        .min(point_segment_distance(a2, b1, b2))
+        .min(point_segment_distance(b1, a1, a2))
+        .min(point_segment_distance(b2, a1, a2))
+}
+
+fn segments_intersect(a1: (f32, f32), a2: (f32, f32), b1: (f32, f32), b2: (f32, f32)) -> bool {
+    let cross = |o: (f32, f32), p: (f32, f32), q: (f32, f32)| {
+        (p.0 - o.0) * (q.1 - o.1) - (p.1 - o.1) * (q.0 - o.0)
+    };
+    let d1 = cross(b1, b2, a1);
+    let d2 = cross(b1, b2, a2);
+    let d3 = cross(a1, a2, b1);
+    let d4 = cross(a1, a2, b2);
+    ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stroke::LineStyle;
+
+    const HALF_WIDTH: f32 = 1.0;
+
+    fn samples(points: &[(f32, f32)]) -> Vec<Sample> {
+        points
+            .iter()
+            .map(|&(x, y)| Sample {
+                x,
+                y,
+                pressure: 1.0,
+                time_ms: 0,
+            })
+            .collect()
+    }
+
+    fn stroke(id: u64, points: &[(f32, f32)]) -> Stroke {
+        let mut stroke = Stroke::new(
+            id,
+            LineStyle {
+                width: HALF_WIDTH,
+                ..LineStyle::default()
+            },
+        );
+        stroke.samples = samples(points);
+        stroke
+    }
+
+    /// Zigzag between y = 0 and y = 30, drawn as a flat line around y = 15
+    /// once heavily smoothed. Its samples at y = 0 are at even tens of x.
+    fn zigzag_points() -> Vec<(f32, f32)> {
+        (0..=20)
+            .map(|i| (i as f32 * 10.0, if i % 2 == 0 { 0.0 } else { 30.0 }))
+            .collect()
+    }
+
+    fn positions(samples: &[Sample]) -> Vec<(f32, f32)> {
+        samples.iter().map(|s| (s.x, s.y)).collect()
+    }
+
+    #[test]
+    fn segment_crossing_the_circle() {
+        let range = segment_circle_intersection((0.0, 0.0), (10.0, 0.0), (5.0, 0.0), 2.0);
+        let (t_in, t_out) = range.unwrap();
+        assert!((t_in - 0.3).abs() < 1e-6 && (t_out - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn segment_starting_in_the_circle() {
+        let range = segment_circle_intersection((0.0, 0.0), (10.0, 0.0), (0.0, 0.0), 2.0);
+        let (t_in, t_out) = range.unwrap();
+        assert!(t_in == 0.0 && (t_out - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn segment_missing_the_circle() {
+        assert_eq!(
+            segment_circle_intersection((0.0, 0.0), (10.0, 0.0), (5.0, 5.0), 2.0),
+            None
+        );
+        // Touching the edge doesn't cut anything
+        assert_eq!(
+            segment_circle_intersection((0.0, 0.0), (10.0, 0.0), (5.0, 2.0), 2.0),
+            None
+        );
+        // The circle is on the line, past the end of the segment
+        assert_eq!(
+            segment_circle_intersection((0.0, 0.0), (10.0, 0.0), (15.0, 0.0), 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn zero_length_segment() {
+        let point = (1.0, 1.0);
+        assert_eq!(
+            segment_circle_intersection(point, point, (0.0, 0.0), 2.0),
+            Some((0.0, 1.0))
+        );
+        assert_eq!(
+            segment_circle_intersection(point, point, (5.0, 0.0), 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn cut_in_the_middle() {
+        let line = stroke(0, &[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)]);
+        // The circle is grown by the half width of the stroke
+        let pieces = cut_samples(&line.samples, &line, (10.0, 0.0), 2.0);
+        let pieces: Vec<_> = pieces.iter().map(|piece| positions(piece)).collect();
+        assert_eq!(
+            pieces,
+            [vec![(0.0, 0.0), (7.0, 0.0)], vec![(13.0, 0.0), (20.0, 0.0)]]
+        );
+    }
+
+    #[test]
+    fn cut_at_an_end() {
+        let line = stroke(0, &[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)]);
+        let pieces = cut_samples(&line.samples, &line, (0.0, 0.0), 2.0);
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(
+            positions(&pieces[0]),
+            [(3.0, 0.0), (10.0, 0.0), (20.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn cut_away_from_the_stroke() {
+        let line = stroke(0, &[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)]);
+        let pieces = cut_samples(&line.samples, &line, (10.0, 10.0), 2.0);
+        assert_eq!(pieces, vec![line.samples]);
+    }
+
+    #[test]
+    fn cut_dots() {
+        let dot = stroke(0, &[(0.0, 0.0)]);
+        assert!(cut_samples(&dot.samples, &dot, (1.0, 1.0), 2.0).is_empty());
+        assert_eq!(
+            cut_samples(&dot.samples, &dot, (5.0, 5.0), 2.0),
+            vec![dot.samples]
+        );
+    }
+
+    #[test]
+    fn smoothed_strokes_are_cut_where_they_are_drawn() {
+        let mut zigzag = stroke(0, &zigzag_points());
+        zigzag.style.smoothing_range = 3;
+        zigzag.style.smoothing_intensity = 3;
+        let drawn = zigzag.smoothed_samples();
+
+        // A raw sample is under the eraser, but no ink
+        let mut strokes = vec![zigzag.clone()];
+        let mut eraser = Eraser::default();
+        let mut next_id = 1;
+        assert!(!eraser.cut_strokes(&mut strokes, 100.0, 0.0, 3.0, &mut next_id));
+        assert!(eraser.finish().is_none());
+
+        // The pieces keep the drawn samples and are not smoothed again
+        assert!(eraser.cut_strokes(&mut strokes, 100.0, 15.0, 3.0, &mut next_id));
+        assert_eq!(strokes.len(), 2);
+        for piece in &strokes {
+            assert_eq!(piece.style.smoothing_range, 0);
+            assert_eq!(piece.order, zigzag.order);
+            assert_eq!(piece.smoothed_samples(), piece.samples);
+        }
+        assert_eq!(strokes[0].samples[..3], drawn[..3]);
+        assert_eq!(strokes[1].samples.last(), drawn.last());
+    }
+
+    #[test]
+    fn smoothed_strokes_are_erased_where_they_are_drawn() {
+        let mut zigzag = stroke(0, &zigzag_points());
+        zigzag.style.smoothing_range = 3;
+        zigzag.style.smoothing_intensity = 3;
+
+        let mut strokes = vec![zigzag];
+        let mut eraser = Eraser::default();
+        assert!(!eraser.erase_strokes(&mut strokes, 100.0, 0.0, 3.0));
+        assert!(eraser.erase_strokes(&mut strokes, 100.0, 15.0, 3.0));
+        assert!(strokes.is_empty());
+    }
+
+    #[test]
+    fn finish_records_the_drag() {
+        let mut eraser = Eraser::default();
+        assert!(eraser.finish().is_none());
+
+        let line = stroke(0, &[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (30.0, 0.0)]);
+        let mut strokes = vec![line.clone()];
+        let mut next_id = 1;
+        assert!(eraser.cut_strokes(&mut strokes, 10.0, 0.0, 2.0, &mut next_id));
+        // The first piece is cut again in the same drag
+        assert!(eraser.cut_strokes(&mut strokes, 3.0, 0.0, 2.0, &mut next_id));
+
+        let change = eraser.finish().unwrap();
+        assert_eq!(change.removed.len(), 1);
+        assert_eq!(change.removed[0].id, line.id);
+        // Pieces that didn't survive the drag never reach the history
+        let added: Vec<u64> = change.added.iter().map(|stroke| stroke.id).collect();
+        let kept: Vec<u64> = strokes.iter().map(|stroke| stroke.id).collect();
+        assert_eq!(added, kept);
+        assert!(!added.contains(&1));
+
+        assert!(eraser.finish().is_none());
+    }
+}
@@ -0,0 +1,331 @@
+use std::f32::consts::PI;
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::raster::Image;
+use crate::shape::{Shape, ShapeKind};
+use crate::stroke::{LineStyle, Sample, Stroke};
+use crate::tessellation::{LineJoin, MIN_SEGMENT_LENGTH};
+use crate::Rect2D;
+
+/// Write an image as an 8 bits RGBA png file
+pub fn write_png(image: &Image, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    Ok(())
+}
+
+/// Write strokes as a svg file, in overlay pixels
+pub fn write_svg(
+    strokes: &[Stroke],
+    overlay_rect: &Rect2D,
+    background: Option<[f32; 4]>,
+    path: &Path,
+) -> io::Result<()> {
+    fs::write(path, svg_document(strokes, overlay_rect, background))
+}
+
+/// Svg document with one element per stroke
+///
+/// Strokes drawn with a constant pressure are paths using the stroke width,
+/// others are filled polygons following their outline. Dots are circles and
+/// shapes are their matching svg elements.
+fn svg_document(strokes: &[Stroke], overlay_rect: &Rect2D, background: Option<[f32; 4]>) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = overlay_rect.width,
+        h = overlay_rect.height
+    );
+
+    if let Some(color) = background {
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="0" y="0" width="{}" height="{}" fill="{}" fill-opacity="{}"/>"#,
+            overlay_rect.width,
+            overlay_rect.height,
+            color_to_hex([color[0], color[1], color[2]]),
+            color[3]
+        );
+    }
+
+    for stroke in strokes {
+        let samples = stroke.smoothed_samples();
+        let first = match samples.first() {
+            Some(first) => *first,
+            None => continue,
+        };
+        let color = color_to_hex(stroke.style.color);
+        let opacity = opacity_attribute(stroke.style.opacity);
+
+        if samples.iter().all(|s| s.x == first.x && s.y == first.y) {
+            let _ = writeln!(
+                svg,
+                r#"  <circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"{}/>"#,
+                first.x,
+                first.y,
+                stroke.style.half_width(first.pressure),
+                color,
+                opacity
+            );
+        } else if let Some(shape) = &stroke.shape {
+            let _ = writeln!(
+                svg,
+                r#"  {} fill="none" stroke="{}" stroke-width="{:.2}" stroke-linecap="round" stroke-linejoin="{}"{}/>"#,
+                svg_shape(shape),
+                color,
+                stroke.style.half_width(first.pressure) * 2.0,
+                svg_line_join(stroke.style.line_join),
+                opacity
+            );
+        } else if samples.iter().all(|s| s.pressure == first.pressure) {
+            let mut d = String::new();
+            for (i, sample) in samples.iter().enumerate() {
+                let command = if i == 0 { 'M' } else { 'L' };
+                let _ = write!(d, "{}{:.2} {:.2} ", command, sample.x, sample.y);
+            }
+            let _ = writeln!(
+                svg,
+                r#"  <path d="{}" fill="none" stroke="{}" stroke-width="{:.2}" stroke-linecap="round" stroke-linejoin="{}"{}/>"#,
+                d.trim_end(),
+                color,
+                stroke.style.half_width(first.pressure) * 2.0,
+                svg_line_join(stroke.style.line_join),
+                opacity
+            );
+        } else {
+            let mut points = String::new();
+            for (x, y) in stroke_outline(&samples, &stroke.style) {
+                let _ = write!(points, "{:.2},{:.2} ", x, y);
+            }
+            let _ = writeln!(
+                svg,
+                r#"  <polygon points="{}" fill="{}"{}/>"#,
+                points.trim_end(),
+                color,
+                opacity
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Outline of a stroke of varying width, as a polygon
+///
+/// Goes along the left side of the stroke, around the round cap at its end,
+/// back along the right side and around the round cap at its start.
+fn stroke_outline(samples: &[Sample], style: &LineStyle) -> Vec<(f32, f32)> {
+    // Repeated positions have no direction, they are skipped like in the tessellation
+    let mut points: Vec<Sample> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        if let Some(previous) = points.last() {
+            if (sample.x - previous.x).hypot(sample.y - previous.y) < MIN_SEGMENT_LENGTH {
+                continue;
+            }
+        }
+        points.push(*sample);
+    }
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Vec::new(),
+    };
+    if points.len() == 1 {
+        let half_width = style.half_width(first.pressure);
+        return arc_points((first.x, first.y), half_width, 0.0, 2.0 * PI);
+    }
+
+    // Direction of the line at each point, between its segments in the middle
+    let segment_directions: Vec<f32> = points
+        .windows(2)
+        .map(|pair| (pair[1].y - pair[0].y).atan2(pair[1].x - pair[0].x))
+        .collect();
+    let directions: Vec<f32> = (0..points.len())
+        .map(|i| {
+            let incoming = segment_directions[i.saturating_sub(1)];
+            let outgoing = segment_directions[i.min(segment_directions.len() - 1)];
+            let (x, y) = (
+                incoming.cos() + outgoing.cos(),
+                incoming.sin() + outgoing.sin(),
+            );
+            // A line turning back on itself keeps the incoming direction
+            if x.hypot(y) < 1e-3 {
+                incoming
+            } else {
+                y.atan2(x)
+            }
+        })
+        .collect();
+
+    let side = |sample: &Sample, direction: f32, sign: f32| {
+        let half_width = style.half_width(sample.pressure) * sign;
+        (
+            sample.x - direction.sin() * half_width,
+            sample.y + direction.cos() * half_width,
+        )
+    };
+    let cap = |sample: &Sample, direction: f32| {
+        let half_width = style.half_width(sample.pressure);
+        let mut cap = arc_points((sample.x, sample.y), half_width, direction + PI / 2.0, -PI);
+        // The ends of the arc are already on the sides
+        cap.pop();
+        cap.remove(0);
+        cap
+    };
+
+    let mut outline = Vec::with_capacity(points.len() * 2);
+    for (sample, &direction) in points.iter().zip(&directions) {
+        outline.push(side(sample, direction, 1.0));
+    }
+    outline.extend(cap(&last, directions[points.len() - 1]));
+    for (sample, &direction) in points.iter().zip(&directions).rev() {
+        outline.push(side(sample, direction, -1.0));
+    }
+    outline.extend(cap(&first, directions[0] + PI));
+    outline
+}
+
+/// Points along a circle arc, angles in radians
+///
+/// Uses as many points as the arcs of the tessellation.
+fn arc_points(center: (f32, f32), radius: f32, start_angle: f32, sweep: f32) -> Vec<(f32, f32)> {
+    let circle_segments = (radius * 2.0).clamp(8.0, 64.0);
+    let segments = (sweep.abs() / (2.0 * PI) * circle_segments).ceil().max(2.0) as usize;
+    (0..=segments)
+        .map(|i| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            (
+                center.0 + angle.cos() * radius,
+                center.1 + angle.sin() * radius,
+            )
+        })
+        .collect()
+}
+
+/// Start of the svg element of a shape, without its style
+fn svg_shape(shape: &Shape) -> String {
+    let (start, end) = (shape.start, shape.end);
+    let ((x, y), (width, height)) = shape.bounds();
+    match shape.kind {
+        ShapeKind::Line => format!(
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}""#,
+            start.0, start.1, end.0, end.1
+        ),
+        ShapeKind::Arrow => {
+            let mut d = format!("M{:.2} {:.2} L{:.2} {:.2}", start.0, start.1, end.0, end.1);
+            if let Some((left, right)) = shape.arrow_head_sides() {
+                let _ = write!(
+                    d,
+                    " M{:.2} {:.2} L{:.2} {:.2} L{:.2} {:.2}",
+                    left.0, left.1, end.0, end.1, right.0, right.1
+                );
+            }
+            format!(r#"<path d="{}""#, d)
+        }
+        ShapeKind::Rectangle => format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}""#,
+            x, y, width, height
+        ),
+        ShapeKind::Ellipse => format!(
+            r#"<ellipse cx="{:.2}" cy="{:.2}" rx="{:.2}" ry="{:.2}""#,
+            x + width / 2.0,
+            y + height / 2.0,
+            width / 2.0,
+            height / 2.0
+        ),
+    }
+}
+
+/// Opacity of a whole element, so a translucent stroke crossing itself
+/// doesn't get darker
+fn opacity_attribute(opacity: f32) -> String {
+    if opacity < 1.0 {
+        format!(r#" opacity="{:.2}""#, opacity)
+    } else {
+        String::new()
+    }
+}
+
+fn svg_line_join(join: LineJoin) -> &'static str {
+    match join {
+        LineJoin::Round => "round",
+        LineJoin::Miter => "miter",
+        LineJoin::Bevel => "bevel",
+    }
+}
+
+fn color_to_hex(color: [f32; 3]) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2])
+    )
+}
+
+/// Path of a new export in `dir`, such as `inke-2020-07-14-153012.png`
+///
+/// The time is in UTC. Exports within the same second get a counter, such as
+/// `inke-2020-07-14-153012-2.png`, instead of replacing each other. The
+/// directory is created if it is missing.
+pub fn export_path(dir: &Path, extension: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = export_file_name();
+    let mut path = dir.join(format!("{}.{}", name, extension));
+    let mut counter = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", name, counter, extension));
+        counter += 1;
+    }
+    Ok(path)
+}
+
+/// Name of an export made now, without extension
+fn export_file_name() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+
+    format!(
+        "inke-{:04}-{:02}-{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) date
+///
+/// Reference: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,135 @@
+use std::time::Instant;
+
+use crate::stroke::Stroke;
+
+/// A reversible change to the list of strokes
+#[derive(Default, Debug)]
+pub struct Change {
+    pub removed: Vec<Stroke>,
+    pub added: Vec<Stroke>,
+}
+
+impl Change {
+    /// Remove then add the strokes of the change
+    fn apply(&self, strokes: &mut Vec<Stroke>) {
+        remove_strokes(strokes, &self.removed);
+        insert_strokes(strokes, &self.added);
+    }
+
+    /// Reverse of `apply`
+    fn revert(&self, strokes: &mut Vec<Stroke>) {
+        remove_strokes(strokes, &self.added);
+        insert_strokes(strokes, &self.removed);
+    }
+}
+
+/// Undo and redo stacks of changes made to the strokes
+///
+/// Undone changes are kept until a new change is pushed.
+#[derive(Default, Debug)]
+pub struct History {
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    max_steps: usize,
+}
+
+impl History {
+    /// History keeping up to `max_steps` undo steps, 0 for unlimited
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            max_steps,
+            ..Default::default()
+        }
+    }
+
+    /// Record a change that was already applied to the strokes
+    ///
+    /// With `merge_with_last`, the change is part of the previous undo step
+    /// instead of creating a new one.
+    pub fn push(&mut self, change: Change, merge_with_last: bool) {
+        let can_merge = merge_with_last && self.redo_stack.is_empty();
+        match self.undo_stack.last_mut() {
+            Some(last) if can_merge => {
+                last.removed.extend(change.removed);
+                last.added.extend(change.added);
+            }
+            _ => self.undo_stack.push(change),
+        }
+        self.redo_stack.clear();
+        self.forget_extra_steps();
+    }
+
+    /// Keep up to `max_steps` undo steps from now on, 0 for unlimited
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+        self.forget_extra_steps();
+    }
+
+    /// Forget the oldest steps
+    fn forget_extra_steps(&mut self) {
+        if self.max_steps > 0 && self.undo_stack.len() > self.max_steps {
+            let n_extra_steps = self.undo_stack.len() - self.max_steps;
+            self.undo_stack.drain(..n_extra_steps);
+        }
+    }
+
+    /// Forget the strokes that expired, as if they were never drawn
+    ///
+    /// Steps left without any change are dropped, so undo and redo skip them.
+    pub fn forget_expired(&mut self, now: Instant) {
+        forget_expired_strokes(&mut self.undo_stack, now);
+        forget_expired_strokes(&mut self.redo_stack, now);
+    }
+
+    /// Revert the last change, returns false if there was nothing to undo
+    ///
+    /// Strokes that faded while only held by the history are skipped.
+    pub fn undo(&mut self, strokes: &mut Vec<Stroke>) -> bool {
+        self.forget_expired(Instant::now());
+        match self.undo_stack.pop() {
+            Some(change) => {
+                change.revert(strokes);
+                self.redo_stack.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply the last undone change, returns false if there was nothing to redo
+    ///
+    /// Strokes that faded while only held by the history are skipped.
+    pub fn redo(&mut self, strokes: &mut Vec<Stroke>) -> bool {
+        self.forget_expired(Instant::now());
+        match self.redo_stack.pop() {
+            Some(change) => {
+                change.apply(strokes);
+                self.undo_stack.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn forget_expired_strokes(stack: &mut Vec<Change>, now: Instant) {
+    for change in stack.iter_mut() {
+        change.removed.retain(|stroke| !stroke.is_expired(now));
+        change.added.retain(|stroke| !stroke.is_expired(now));
+    }
+    stack.retain(|change| !change.removed.is_empty() || !change.added.is_empty());
+}
+
+fn remove_strokes(strokes: &mut Vec<Stroke>, to_remove: &[Stroke]) {
+    strokes.retain(|stroke| !to_remove.iter().any(|removed| removed.id == stroke.id));
+}
+
+/// Strokes are kept sorted by drawing order so they are put back at their original depth
+///
+/// Pieces of a cut stroke share its order and are sorted among themselves by id.
+fn insert_strokes(strokes: &mut Vec<Stroke>, to_insert: &[Stroke]) {
+    for stroke in to_insert {
+        let index = strokes.partition_point(|s| (s.order, s.id) < (stroke.order, stroke.id));
+        strokes.insert(index, stroke.clone());
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+
+use glutin::event::VirtualKeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::shape::ShapeKind;
+
+/// Something a shortcut can do
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Help,
+    ToggleBackground,
+    TogglePressurePreview,
+    ToggleHighlighter,
+    ToggleStrokeEraser,
+    TogglePartialEraser,
+    ToggleLaser,
+    ToggleAutoFade,
+    Clear,
+    Undo,
+    Redo,
+    SaveSession,
+    OpenSession,
+    RestoreRecovery,
+    ExportPng,
+    ExportSvg,
+    BrushColor(usize), // Index in the brush colors
+    NextColor,
+    PreviousColor,
+    BrushSize(usize), // Index in the brush sizes
+    NextSize,
+    PreviousSize,
+    Shape(ShapeKind),
+}
+
+/// Key pressed with its modifiers
+///
+/// `ctrl` also matches the logo key (cmd on MacOSX).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+/// Shortcuts from the config, ready to be looked up
+#[derive(Default, Debug)]
+pub struct Keybindings {
+    actions: HashMap<KeyCombo, Action>,
+    names: HashMap<KeyCombo, String>, // Spelling of the combos in the config
+}
+
+impl Keybindings {
+    /// Parse the key combos of the config
+    ///
+    /// Invalid combos and combos bound twice to different actions are
+    /// skipped and returned as errors. Different spellings of a combo are
+    /// read in alphabetical order, the first one wins.
+    pub fn new(bindings: &BTreeMap<String, Action>) -> (Self, Vec<String>) {
+        let mut actions = HashMap::new();
+        let mut names: HashMap<KeyCombo, String> = HashMap::new();
+        let mut errors = Vec::new();
+
+        for (name, action) in bindings {
+            let combo = match parse_key_combo(name) {
+                Ok(combo) => combo,
+                Err(error) => {
+                    errors.push(format!("Invalid keybinding '{}': {}", name, error));
+                    continue;
+                }
+            };
+
+            match actions.get(&combo) {
+                Some(bound_action) if bound_action != action => errors.push(format!(
+                    "Keybinding '{}' conflicts with '{}', {:?} is ignored",
+                    name, names[&combo], action
+                )),
+                Some(_) => (),
+                None => {
+                    actions.insert(combo, *action);
+                    names.insert(combo, name.clone());
+                }
+            }
+        }
+
+        (Self { actions, names }, errors)
+    }
+
+    /// Combo bound to an action as written in the config, the first one in
+    /// alphabetical order if there are several
+    pub fn shortcut(&self, action: Action) -> Option<&str> {
+        self.actions
+            .iter()
+            .filter(|(_, bound_action)| **bound_action == action)
+            .map(|(combo, _)| self.names[combo].as_str())
+            .min()
+    }
+
+    pub fn action(&self, combo: &KeyCombo) -> Option<Action> {
+        self.actions.get(combo).copied()
+    }
+}
+
+/// Shortcuts of the qwerty layout
+pub fn default_keybindings() -> BTreeMap<String, Action> {
+    let mut bindings = vec![
+        ("escape", Action::Quit),
+        ("h", Action::Help),
+        ("b", Action::ToggleBackground),
+        ("p", Action::TogglePressurePreview),
+        ("g", Action::ToggleHighlighter),
+        ("x", Action::ToggleStrokeEraser),
+        ("c", Action::TogglePartialEraser),
+        ("l", Action::ToggleLaser),
+        ("f", Action::ToggleAutoFade),
+        ("space", Action::Clear),
+        ("ctrl+z", Action::Undo),
+        ("ctrl+shift+z", Action::Redo),
+        ("ctrl+y", Action::Redo),
+        ("ctrl+alt+s", Action::SaveSession),
+        ("ctrl+o", Action::OpenSession),
+        ("ctrl+r", Action::RestoreRecovery),
+        ("ctrl+s", Action::ExportPng),
+        ("ctrl+shift+s", Action::ExportSvg),
+        ("tab", Action::NextColor),
+        ("shift+tab", Action::PreviousColor),
+        ("rbracket", Action::NextSize),
+        ("lbracket", Action::PreviousSize),
+        ("v", Action::Shape(ShapeKind::Line)),
+        ("a", Action::Shape(ShapeKind::Arrow)),
+        ("s", Action::Shape(ShapeKind::Rectangle)),
+        ("o", Action::Shape(ShapeKind::Ellipse)),
+    ];
+    // q,w,e,r,... for line colors
+    for (i, key) in ["q", "w", "e", "r", "t", "y", "u", "i"].iter().enumerate() {
+        bindings.push((key, Action::BrushColor(i)));
+    }
+    // 1,2,3,... for size
+    for (i, key) in ["1", "2", "3", "4", "5"].iter().enumerate() {
+        bindings.push((key, Action::BrushSize(i)));
+    }
+
+    bindings
+        .into_iter()
+        .map(|(name, action)| (name.to_string(), action))
+        .collect()
+}
+
+/// Parse a combo such as `ctrl+shift+z`, case insensitive
+pub fn parse_key_combo(name: &str) -> Result<KeyCombo, String> {
+    let mut key = None;
+    let mut combo = KeyCombo {
+        key: VirtualKeyCode::Escape,
+        ctrl: false,
+        shift: false,
+        alt: false,
+    };
+
+    for part in name.split('+').map(|part| part.trim().to_lowercase()) {
+        match part.as_str() {
+            "ctrl" | "control" | "cmd" | "logo" | "super" => combo.ctrl = true,
+            "shift" => combo.shift = true,
+            "alt" | "option" => combo.alt = true,
+            _ if key.is_some() => return Err("more than one key".to_string()),
+            _ => match key_from_name(&part) {
+                Some(code) => key = Some(code),
+                None => return Err(format!("unknown key '{}'", part)),
+            },
+        }
+    }
+
+    match key {
+        Some(key) => Ok(KeyCombo { key, ..combo }),
+        None => Err("no key".to_string()),
+    }
+}
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+
+    let key = match name {
+        "a" => A,
+        "b" => B,
+        "c" => C,
+        "d" => D,
+        "e" => E,
+        "f" => F,
+        "g" => G,
+        "h" => H,
+        "i" => I,
+        "j" => J,
+        "k" => K,
+        "l" => L,
+        "m" => M,
+        "n" => N,
+        "o" => O,
+        "p" => P,
+        "q" => Q,
+        "r" => R,
+        "s" => S,
+        "t" => T,
+        "u" => U,
+        "v" => V,
+        "w" => W,
+        "x" => X,
+        "y" => Y,
+        "z" => Z,
+        "0" => Key0,
+        "1" => Key1,
+        "2" => Key2,
+        "3" => Key3,
+        "4" => Key4,
+        "5" => Key5,
+        "6" => Key6,
+        "7" => Key7,
+        "8" => Key8,
+        "9" => Key9,
+        "numpad0" => Numpad0,
+        "numpad1" => Numpad1,
+        "numpad2" => Numpad2,
+        "numpad3" => Numpad3,
+        "numpad4" => Numpad4,
+        "numpad5" => Numpad5,
+        "numpad6" => Numpad6,
+        "numpad7" => Numpad7,
+        "numpad8" => Numpad8,
+        "numpad9" => Numpad9,
+        "f1" => F1,
+        "f2" => F2,
+        "f3" => F3,
+        "f4" => F4,
+        "f5" => F5,
+        "f6" => F6,
+        "f7" => F7,
+        "f8" => F8,
+        "f9" => F9,
+        "f10" => F10,
+        "f11" => F11,
+        "f12" => F12,
+        "escape" | "esc" => Escape,
+        "space" => Space,
+        "enter" | "return" => Return,
+        "tab" => Tab,
+        "backspace" => Back,
+        "delete" => Delete,
+        "insert" => Insert,
+        "home" => Home,
+        "end" => End,
+        "pageup" => PageUp,
+        "pagedown" => PageDown,
+        "left" => Left,
+        "right" => Right,
+        "up" => Up,
+        "down" => Down,
+        "minus" => Minus,
+        "subtract" => Subtract,
+        "add" => Add,
+        "equals" => Equals,
+        "comma" => Comma,
+        "period" => Period,
+        "slash" => Slash,
+        "backslash" => Backslash,
+        "semicolon" => Semicolon,
+        "apostrophe" => Apostrophe,
+        "grave" => Grave,
+        "lbracket" => LBracket,
+        "rbracket" => RBracket,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use crate::stroke::{LineStyle, Sample, Stroke};
+
+/// Time between two redraws of fading trails, about 60 per second
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Strokes of the laser pointer
+///
+/// They are kept apart from the drawing so they never reach the undo
+/// history, the sessions or the exports. Once released, a stroke fades out
+/// over the fade duration and is removed.
+pub struct Laser {
+    fade_duration: Duration,
+    current_stroke: Option<Stroke>,
+    trails: Vec<(Stroke, Instant)>, // Released strokes, with the time of their release
+    last_frame: Instant,
+}
+
+impl Laser {
+    pub fn new(fade_duration: Duration) -> Self {
+        Self {
+            fade_duration,
+            current_stroke: None,
+            trails: Vec::new(),
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn set_fade_duration(&mut self, fade_duration: Duration) {
+        self.fade_duration = fade_duration;
+    }
+
+    /// Extend the stroke being drawn, or start one with the style
+    pub fn add_sample(&mut self, sample: Sample, style: &LineStyle) {
+        self.current_stroke
+            .get_or_insert_with(|| Stroke::new(0, style.clone()))
+            .samples
+            .push(sample);
+    }
+
+    /// The stroke being drawn is released, it starts fading
+    pub fn end_stroke(&mut self) {
+        if let Some(stroke) = self.current_stroke.take() {
+            self.trails.push((stroke, Instant::now()));
+        }
+    }
+
+    /// Time at which the trails need to be drawn again, if some are fading
+    pub fn next_frame(&self) -> Option<Instant> {
+        if self.trails.is_empty() {
+            None
+        } else {
+            Some(self.last_frame + FRAME_INTERVAL)
+        }
+    }
+
+    /// Strokes to draw now, the trails more transparent as they fade
+    ///
+    /// Trails done fading are removed.
+    pub fn frame_strokes(&mut self) -> Vec<Stroke> {
+        let now = Instant::now();
+        let fade_duration = self.fade_duration;
+        self.last_frame = now;
+        self.trails
+            .retain(|(_, released)| now.duration_since(*released) < fade_duration);
+
+        let mut strokes: Vec<Stroke> = self
+            .trails
+            .iter()
+            .map(|(stroke, released)| {
+                let fade =
+                    now.duration_since(*released).as_secs_f32() / fade_duration.as_secs_f32();
+                let mut stroke = stroke.clone();
+                stroke.style.opacity *= 1.0 - fade;
+                stroke
+            })
+            .collect();
+        strokes.extend(self.current_stroke.clone());
+        strokes
+    }
+}
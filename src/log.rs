@@ -0,0 +1,36 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Messages are also written to a file since release builds on Windows have
+/// no console to print to
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Write the messages of this launch to `path`, replacing the last ones
+pub fn init(path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    match File::create(path) {
+        Ok(file) => *LOG_FILE.lock().unwrap() = Some(file),
+        Err(error) => eprintln!("Failed to create log file {}: {}", path.display(), error),
+    }
+}
+
+pub fn info(message: fmt::Arguments) {
+    println!("{}", message);
+    write_line(message);
+}
+
+pub fn error(message: fmt::Arguments) {
+    eprintln!("{}", message);
+    write_line(message);
+}
+
+fn write_line(message: fmt::Arguments) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        let _ = writeln!(file, "{}", message);
+    }
+}
@@ -3,11 +3,27 @@
 extern crate gl;
 extern crate glutin;
 
-use std::f32::consts::{FRAC_PI_2, PI};
+mod autosave;
+mod cli;
+mod config;
+mod eraser;
+mod export;
+mod history;
+mod keybindings;
+mod laser;
+mod log;
+mod palette;
+mod raster;
+mod session;
+mod shape;
+mod stroke;
+mod tessellation;
+
+use std::f32::consts::PI;
 use std::ffi::CStr;
 use std::ffi::CString;
-use std::io::Write;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use std::{fs, mem, ptr, str};
 
 use serde::{Deserialize, Serialize};
@@ -15,56 +31,37 @@ use serde::{Deserialize, Serialize};
 use gl::types::*;
 use glutin::dpi::{PhysicalPosition, PhysicalSize};
 use glutin::event::{
-    ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+    DeviceId, ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
 };
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::monitor::MonitorHandle;
 use glutin::window::Window;
 use glutin::ContextWrapper;
 
+use autosave::Autosave;
+use config::{find_config_file, load_config, Config};
+use eraser::Eraser;
+use history::{Change, History};
+use keybindings::{Action, KeyCombo, Keybindings};
+use laser::Laser;
+use palette::BrushSize;
+use session::Session;
+use shape::{Shape, ShapeKind};
+use stroke::{LineStyle, Sample, Stroke, VERTEX_SIZE};
+
 // Shader sources
-static VS_SRC: &'static str = include_str!("shader.vert");
-static FS_SRC: &'static str = include_str!("shader.frag");
+static VS_SRC: &str = include_str!("shader.vert");
+static FS_SRC: &str = include_str!("shader.frag");
 
 const N_CURSOR_RETICLE_POINTS: usize = 32;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Config {
-    config_version: u8,
-    smoothing_range: usize,
-    smoothing_intensity: usize,
-    default_brush_size: f32,
-    default_brush_color_index: i32,
-    brush_colors: [[u32; 3]; 8],
-    brush_sizes: [f32; 5],
-    background_color: [u32; 3],
-    background_color_opacity: f32,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            config_version: 1,
-            smoothing_range: 1,
-            smoothing_intensity: 1,
-            default_brush_size: 3.0,
-            default_brush_color_index: 0,
-            brush_colors: [
-                [255, 255, 255], // white
-                [10, 10, 10],    // black
-                [255, 150, 0],   // orange
-                [255, 0, 220],   // pink
-                [255, 50, 50],   // red
-                [25, 255, 75],   // green
-                [25, 75, 255],   // blue
-                [255, 255, 0],   // yellow
-            ],
-            brush_sizes: [1.0, 3.0, 5.0, 10.0, 30.0],
-            background_color: [0, 0, 0],
-            background_color_opacity: 0.8,
-        }
-    }
-}
+const DEFAULT_SESSION_FILE: &str = "session.inke";
+const RECOVERY_FILE: &str = "recovery.inke";
+const PREVIOUS_RECOVERY_FILE: &str = "recovery-previous.inke";
+const PRESSURE_PREVIEW_SAMPLES: usize = 64;
+const PRESSURE_PREVIEW_LENGTH: f32 = 300.0;
+const LOG_FILE: &str = "inke.log";
+const ERASER_RETICLE_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+const STROKE_DEPTH_STEP: f32 = 1.0 / (1 << 20) as f32;
 
 #[derive(Default)]
 struct Input {
@@ -84,13 +81,16 @@ struct Modifiers {
 struct Cursor {
     x: f32,
     y: f32,
-    last_x: f32,
-    last_y: f32,
     pressed: bool,
-    released_time: Option<SystemTime>,
+    is_eraser_button: bool, // Pressed with the button that always erases
+    pressure: Option<f32>,  // Pressure of the last sample, if the device has one
+    pressure_device: Option<DeviceId>, // Device drawing a touch stroke, other devices are ignored
+    touch_id: Option<u64>,  // Finger or pen drawing a touch stroke, others are ignored
+    pressed_time: Option<SystemTime>,
+    released_time: Option<SystemTime>, // End of the last pen stroke
 }
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct Rect2D {
     x: f32,
     y: f32,
@@ -112,18 +112,24 @@ struct Point {
 }
 
 impl Point {
-    fn into_array(&self) -> [f32; 3] {
+    fn into_array(self) -> [f32; 3] {
         [self.x, self.y, self.z]
     }
 }
 
-#[derive(Default)]
-struct LineStyle {
-    color: [f32; 3],
-    width: f32,
-    pressure: f32,
-    smoothing_range: usize,
-    smoothing_intensity: usize,
+/// Events sent to the event loop from other threads
+#[derive(Debug)]
+enum UserEvent {
+    ConfigChanged,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Tool {
+    Pen,
+    StrokeEraser,
+    PartialEraser,
+    Laser,
+    Shape(ShapeKind),
 }
 
 struct GLState {
@@ -137,14 +143,30 @@ struct GLState {
 
 struct DrawingState {
     config: Config,
+    config_path: PathBuf,
     need_redraw: bool,
     is_window_hidden: bool,
     is_background_visible: bool,
-    n_points_current_line: u32,
+    is_pressure_preview_visible: bool,
+    is_highlighter: bool,
+    is_auto_fade: bool,
+    need_tessellation: bool,
     line_style: LineStyle,
+    brush_color_index: usize,
+    brush_size_index: usize,
+    tool: Tool,
+    eraser: Eraser,
     gl_context: GLState,
-    undo_steps: Vec<usize>,
-    smooth_index: usize,
+    strokes: Vec<Stroke>,
+    current_stroke: Option<Stroke>,
+    is_current_stroke_merged: bool,
+    next_stroke_id: u64,
+    history: History,
+    session_path: PathBuf,
+    previous_recovery_path: PathBuf,
+    autosave: Autosave,
+    laser: Laser,
+    keybindings: Keybindings,
     vertices: Vec<f32>,
     rect: Rect2D,
 }
@@ -166,8 +188,7 @@ fn compile_shader(src: &str, ty: GLenum) -> GLuint {
         if status != (gl::TRUE as GLint) {
             let mut len = 0;
             gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+            let mut buf = vec![0u8; (len as usize) - 1]; // subtract 1 to skip the trailing null character
             gl::GetShaderInfoLog(
                 shader,
                 len,
@@ -176,9 +197,7 @@ fn compile_shader(src: &str, ty: GLenum) -> GLuint {
             );
             panic!(
                 "{}",
-                str::from_utf8(&buf)
-                    .ok()
-                    .expect("ShaderInfoLog not valid utf8")
+                str::from_utf8(&buf).expect("ShaderInfoLog not valid utf8")
             );
         }
     }
@@ -199,8 +218,7 @@ fn link_program(vs: GLuint, fs: GLuint) -> GLuint {
         if status != (gl::TRUE as GLint) {
             let mut len: GLint = 0;
             gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
+            let mut buf = vec![0u8; (len as usize) - 1]; // subtract 1 to skip the trailing null character
             gl::GetProgramInfoLog(
                 program,
                 len,
@@ -209,9 +227,7 @@ fn link_program(vs: GLuint, fs: GLuint) -> GLuint {
             );
             panic!(
                 "{}",
-                str::from_utf8(&buf)
-                    .ok()
-                    .expect("ProgramInfoLog not valid utf8")
+                str::from_utf8(&buf).expect("ProgramInfoLog not valid utf8")
             );
         }
         program
@@ -225,16 +241,15 @@ fn screen_size_to_gl(w: f32, h: f32, overlay_rect: &Rect2D) -> Size2D {
     }
 }
 
-#[allow(dead_code)]
 fn screen_point_to_gl(x: f32, y: f32, overlay_rect: &Rect2D) -> Point {
     Point {
-        x: x / overlay_rect.width * 2.0,
-        y: y / overlay_rect.height * 2.0,
+        x: x / overlay_rect.width * 2.0 - 1.0,
+        y: y / overlay_rect.height * -2.0 + 1.0,
         z: 0.0,
     }
 }
 
-fn init_gl_window(event_loop: &EventLoop<()>, overlay_rect: &Rect2D) -> GLState {
+fn init_gl_window(event_loop: &EventLoop<UserEvent>, overlay_rect: &Rect2D) -> GLState {
     let window_builder = glutin::window::WindowBuilder::new()
         .with_title("Inke")
         .with_inner_size(PhysicalSize::new(overlay_rect.width, overlay_rect.height))
@@ -245,7 +260,8 @@ fn init_gl_window(event_loop: &EventLoop<()>, overlay_rect: &Rect2D) -> GLState
 
     let gl_window = glutin::ContextBuilder::new()
         .with_multisampling(8)
-        .build_windowed(window_builder, &event_loop)
+        .with_depth_buffer(24)
+        .build_windowed(window_builder, event_loop)
         .unwrap();
 
     let gl_window = unsafe { gl_window.make_current() }.unwrap();
@@ -289,12 +305,12 @@ fn init_gl_window(event_loop: &EventLoop<()>, overlay_rect: &Rect2D) -> GLState
         );
         gl::EnableVertexAttribArray(pos_attr as GLuint);
         gl::VertexAttribPointer(
-            pos_attr as GLuint,                                   // index of attribute
-            3,                                                    // the number of components
-            gl::FLOAT,                                            // data type
-            gl::FALSE as GLboolean,                               // normalized
-            (6 * std::mem::size_of::<f32>()) as gl::types::GLint, // stride (byte offset)
-            ptr::null(),                                          // offset of the first component
+            pos_attr as GLuint,     // index of attribute
+            3,                      // the number of components
+            gl::FLOAT,              // data type
+            gl::FALSE as GLboolean, // normalized
+            (VERTEX_SIZE * std::mem::size_of::<f32>()) as gl::types::GLint, // stride (byte offset)
+            ptr::null(),            // offset of the first component
         );
 
         // vertex_color attrib
@@ -304,113 +320,41 @@ fn init_gl_window(event_loop: &EventLoop<()>, overlay_rect: &Rect2D) -> GLState
         );
         gl::EnableVertexAttribArray(color_attr as GLuint);
         gl::VertexAttribPointer(
-            color_attr as GLuint,                                 // index of attribute
-            3,                                                    // the number of components
-            gl::FLOAT,                                            // data type
-            gl::FALSE as GLboolean,                               // normalized
-            (6 * std::mem::size_of::<f32>()) as gl::types::GLint, // stride (byte offset)
+            color_attr as GLuint,   // index of attribute
+            4,                      // the number of components
+            gl::FLOAT,              // data type
+            gl::FALSE as GLboolean, // normalized
+            (VERTEX_SIZE * std::mem::size_of::<f32>()) as gl::types::GLint, // stride (byte offset)
             (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid, // offset of the first component
         );
+
+        // Translucent strokes blend over the ones below them
+        gl::Enable(gl::BLEND);
+        gl::BlendFuncSeparate(
+            gl::SRC_ALPHA,
+            gl::ONE_MINUS_SRC_ALPHA,
+            gl::ONE,
+            gl::ONE_MINUS_SRC_ALPHA,
+        );
+        // A pixel is only drawn once per stroke, see `stroke_depth`
+        gl::DepthFunc(gl::LESS);
     };
 
     GLState {
         window_context: gl_window,
-        program: program,
-        vs: vs,
-        fs: fs,
-        vbo: vbo,
-        vao: vao,
-    }
-}
-
-/// Apply line smoothing to parts of a point list
-///
-/// Reference: https://stackoverflow.com/a/18830268
-fn apply_line_smoothing(points: &mut [f32], smoothing_range: usize) {
-    if smoothing_range == 0 {
-        return;
-    }
-
-    // Number of line endings to parse
-    let line_segment_len = 3 * 2 * 6; // 3 (points per triangle) * 2 (triangle) * 6 (properties x,y,z,r,g,b)
-    let n_points = (points.len() / line_segment_len) - 1; // -1 to skip last
-
-    // skip first
-    for i in 1..n_points {
-        let start = if i >= smoothing_range {
-            i - smoothing_range
-        } else {
-            0
-        };
-        let end = if i + smoothing_range < n_points {
-            i + smoothing_range
-        } else {
-            n_points
-        };
-
-        // sums for left side of line (p3, p1)
-        let mut sum_x1 = 0.0_f32;
-        let mut sum_y1 = 0.0_f32;
-        // sums for right side of line (p4, p2)
-        let mut sum_x2 = 0.0_f32;
-        let mut sum_y2 = 0.0_f32;
-
-        for j in start..end {
-            // x,y of p3 for that line segment
-            sum_x1 += points[j * line_segment_len];
-            sum_y1 += points[j * line_segment_len + 1];
-            sum_x2 += points[j * line_segment_len + 30];
-            sum_y2 += points[j * line_segment_len + 30 + 1];
-        }
-
-        let avg_x1 = sum_x1 / ((end - start) as f32);
-        let avg_y1 = sum_y1 / ((end - start) as f32);
-        let avg_x2 = sum_x2 / ((end - start) as f32);
-        let avg_y2 = sum_y2 / ((end - start) as f32);
-
-        /*
-          0  18__ 30
-           |\­  \ |
-           | \  \|
-          12¯¯6   24
-
-         36  54__ 66
-           |\­  \ |
-           | \  \|
-          48¯¯42  60
-        */
-
-        // LEFT SIDE
-        // p3 - 24 = p1 of last segment
-        points[i * line_segment_len - 24] = avg_x1;
-        points[i * line_segment_len - 24 + 1] = avg_y1;
-
-        // p3
-        points[i * line_segment_len] = avg_x1;
-        points[i * line_segment_len + 1] = avg_y1;
-
-        // p3 (second triange)
-        points[i * line_segment_len + 18] = avg_x1;
-        points[i * line_segment_len + 18 + 1] = avg_y1;
-
-        // RIGHT SIDE
-        // p4 - 60 = p2 of last segment (first triangle)
-        points[i * line_segment_len + 30 - 60] = avg_x2;
-        points[i * line_segment_len + 30 - 60 + 1] = avg_y2;
-        // p4 - 42 = p2 of last segment (second triangle)
-        points[i * line_segment_len + 30 - 42] = avg_x2;
-        points[i * line_segment_len + 30 - 42 + 1] = avg_y2;
-        // p4
-        points[i * line_segment_len + 30] = avg_x2;
-        points[i * line_segment_len + 30 + 1] = avg_y2;
+        program,
+        vs,
+        fs,
+        vbo,
+        vao,
     }
 }
 
 fn get_overlay_rect(monitors: impl Iterator<Item = MonitorHandle>) -> Rect2D {
-    let mut min_x: i32 = 0;
-    let mut min_y: i32 = 0;
-    let mut max_x: i32 = 0;
-    let mut max_y: i32 = 0;
+    let mut min_x: i32 = i32::MAX;
+    let mut min_y: i32 = i32::MAX;
+    let mut max_x: i32 = i32::MIN;
+    let mut max_y: i32 = i32::MIN;
 
     for monitor in monitors {
         // println!(
@@ -435,6 +379,11 @@ fn get_overlay_rect(monitors: impl Iterator<Item = MonitorHandle>) -> Rect2D {
         }
     }
 
+    // No monitors
+    if min_x > max_x || min_y > max_y {
+        return Rect2D::default();
+    }
+
     Rect2D {
         x: min_x as f32,
         y: min_y as f32,
@@ -443,16 +392,664 @@ fn get_overlay_rect(monitors: impl Iterator<Item = MonitorHandle>) -> Rect2D {
     }
 }
 
+/// Add the current cursor position to the stroke being drawn,
+/// starting a new stroke if needed
+fn add_stroke_sample(drawing: &mut DrawingState, input: &Input) {
+    if drawing.is_window_hidden {
+        return;
+    }
+
+    if drawing.current_stroke.is_none() {
+        // New stroke, it shares the undo step of the last one if it closely follows it
+        drawing.is_current_stroke_merged = match input.cursor.released_time {
+            Some(released_time) => released_time.elapsed().unwrap_or_default().as_millis() <= 200,
+            None => false,
+        };
+
+        drawing.current_stroke = Some(Stroke::new(
+            drawing.next_stroke_id,
+            drawing.line_style.clone(),
+        ));
+        drawing.next_stroke_id += 1;
+    }
+
+    if let Some(stroke) = drawing.current_stroke.as_mut() {
+        stroke.samples.push(cursor_sample(input));
+    }
+}
+
+/// Add the current cursor position to the laser stroke being drawn
+fn add_laser_sample(drawing: &mut DrawingState, input: &Input) {
+    if drawing.is_window_hidden {
+        return;
+    }
+    drawing
+        .laser
+        .add_sample(cursor_sample(input), &drawing.line_style);
+}
+
+/// Start a shape at the cursor, or stretch the shape being drawn to it
+fn drag_shape(drawing: &mut DrawingState, input: &Input, kind: ShapeKind) {
+    if drawing.is_window_hidden {
+        return;
+    }
+
+    if drawing.current_stroke.is_none() {
+        // A shape is always an undo step of its own
+        drawing.is_current_stroke_merged = false;
+
+        let mut style = drawing.line_style.clone();
+        style.smoothing_range = 0; // Corners stay sharp
+        let arrow_head = drawing.config.arrow_head.for_width(style.width);
+        let mut stroke = Stroke::new(drawing.next_stroke_id, style);
+        stroke.shape = Some(Shape::new(
+            kind,
+            (input.cursor.x, input.cursor.y),
+            arrow_head,
+        ));
+        drawing.current_stroke = Some(stroke);
+        drawing.next_stroke_id += 1;
+    }
+
+    if let Some(stroke) = drawing.current_stroke.as_mut() {
+        if let Some(shape) = stroke.shape.as_mut() {
+            shape.end = (input.cursor.x, input.cursor.y);
+            stroke.samples = shape.samples();
+        }
+    }
+}
+
+fn cursor_sample(input: &Input) -> Sample {
+    let time_ms = match input.cursor.pressed_time {
+        Some(pressed_time) => pressed_time.elapsed().unwrap_or_default().as_millis() as u32,
+        None => 0,
+    };
+
+    Sample {
+        x: input.cursor.x,
+        y: input.cursor.y,
+        pressure: input.cursor.pressure.unwrap_or(1.0),
+        time_ms,
+    }
+}
+
+/// Move the stroke being drawn to the list of finished strokes
+fn end_stroke(drawing: &mut DrawingState) {
+    if let Some(mut stroke) = drawing.current_stroke.take() {
+        if drawing.is_auto_fade {
+            stroke.expires_at =
+                Some(Instant::now() + Duration::from_secs(drawing.config.auto_fade_delay));
+        }
+        drawing.strokes.push(stroke.clone());
+        drawing.history.push(
+            Change {
+                added: vec![stroke],
+                ..Default::default()
+            },
+            drawing.is_current_stroke_merged,
+        );
+        drawing.need_tessellation = true;
+    }
+    drawing.need_redraw = true;
+}
+
+/// Remove the auto-fading strokes whose time is up, without an undo step
+fn expire_strokes(drawing: &mut DrawingState) {
+    let now = Instant::now();
+    let n_strokes = drawing.strokes.len();
+    drawing.strokes.retain(|stroke| !stroke.is_expired(now));
+    drawing.history.forget_expired(now);
+    if drawing.strokes.len() != n_strokes {
+        drawing.need_tessellation = true;
+        drawing.need_redraw = true;
+    }
+}
+
+/// Time at which the next auto-fading stroke disappears
+fn next_expiry(drawing: &DrawingState) -> Option<Instant> {
+    drawing
+        .strokes
+        .iter()
+        .filter_map(|stroke| stroke.expires_at)
+        .min()
+}
+
+/// Remove the strokes under the cursor
+fn erase_strokes(drawing: &mut DrawingState, input: &Input) {
+    if drawing.is_window_hidden {
+        return;
+    }
+
+    if drawing.eraser.erase_strokes(
+        &mut drawing.strokes,
+        input.cursor.x,
+        input.cursor.y,
+        drawing.line_style.width,
+    ) {
+        drawing.need_tessellation = true;
+    }
+}
+
+/// Cut the parts of the strokes under the cursor
+fn cut_strokes(drawing: &mut DrawingState, input: &Input) {
+    if drawing.is_window_hidden {
+        return;
+    }
+
+    if drawing.eraser.cut_strokes(
+        &mut drawing.strokes,
+        input.cursor.x,
+        input.cursor.y,
+        drawing.line_style.width,
+        &mut drawing.next_stroke_id,
+    ) {
+        drawing.need_tessellation = true;
+    }
+}
+
+/// Record the strokes changed by the eraser drag as a single undo step
+fn end_erase(drawing: &mut DrawingState) {
+    if let Some(change) = drawing.eraser.finish() {
+        drawing.history.push(change, false);
+    }
+    drawing.need_redraw = true;
+}
+
+/// Tool used by the current cursor press
+///
+/// Shift turns the pen into the line tool, and a shape keeps its tool
+/// until it is released.
+fn active_tool(drawing: &DrawingState, input: &Input) -> Tool {
+    let shape = drawing
+        .current_stroke
+        .as_ref()
+        .and_then(|stroke| stroke.shape);
+    if input.cursor.is_eraser_button {
+        Tool::StrokeEraser
+    } else if let Some(shape) = shape {
+        Tool::Shape(shape.kind)
+    } else if drawing.tool == Tool::Pen && input.modifiers.shift && drawing.current_stroke.is_none()
+    {
+        Tool::Shape(ShapeKind::Line)
+    } else {
+        drawing.tool
+    }
+}
+
+fn is_eraser(tool: Tool) -> bool {
+    tool == Tool::StrokeEraser || tool == Tool::PartialEraser
+}
+
+/// Cursor moved while pressed
+fn handle_drag(drawing: &mut DrawingState, input: &Input) {
+    match active_tool(drawing, input) {
+        Tool::Pen => add_stroke_sample(drawing, input),
+        Tool::StrokeEraser => erase_strokes(drawing, input),
+        Tool::PartialEraser => cut_strokes(drawing, input),
+        Tool::Laser => add_laser_sample(drawing, input),
+        Tool::Shape(kind) => drag_shape(drawing, input, kind),
+    }
+}
+
+/// Cursor released, finish whatever the active tool was doing
+fn handle_release(drawing: &mut DrawingState, input: &mut Input) {
+    input.cursor.pressed = false;
+    input.cursor.is_eraser_button = false;
+    // Only a pen stroke can share its undo step with the next one
+    let is_pen_stroke = drawing
+        .current_stroke
+        .as_ref()
+        .is_some_and(|stroke| stroke.shape.is_none());
+    input.cursor.released_time = if is_pen_stroke {
+        Some(SystemTime::now())
+    } else {
+        None
+    };
+    input.cursor.pressure_device = None;
+    input.cursor.touch_id = None;
+    end_stroke(drawing);
+    end_erase(drawing);
+    drawing.laser.end_stroke();
+}
+
+/// Background color and opacity
+fn background_to_gl(config: &Config) -> [f32; 4] {
+    let bg_color_gl = color_to_gl(config.background_color);
+    [
+        bg_color_gl[0],
+        bg_color_gl[1],
+        bg_color_gl[2],
+        config.background_color_opacity,
+    ]
+}
+
+/// Background color and opacity to export, if it is visible
+fn exported_background(drawing: &DrawingState) -> Option<[f32; 4]> {
+    if drawing.is_background_visible {
+        Some(background_to_gl(&drawing.config))
+    } else {
+        None
+    }
+}
+
+/// Render a session file to a png or svg file, without opening a window
+fn export_session(
+    config: &Config,
+    session_path: &Path,
+    output_path: &Path,
+    is_background_visible: bool,
+) -> std::io::Result<()> {
+    let session = Session::load(session_path)?;
+    let overlay_rect = session.overlay_rect.clone();
+    let strokes = session.strokes_for_overlay(&overlay_rect);
+    let background = if session.is_background_visible || is_background_visible {
+        Some(background_to_gl(config))
+    } else {
+        None
+    };
+
+    match output_path.extension().and_then(|e| e.to_str()) {
+        Some("png") => export::write_png(
+            &raster::rasterize(&strokes, &overlay_rect, background),
+            output_path,
+        ),
+        Some("svg") => export::write_svg(&strokes, &overlay_rect, background, output_path),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the export file must end with .png or .svg",
+        )),
+    }
+}
+
+/// Path of a new export in the export directory, relative to the config file
+fn export_path(drawing: &DrawingState, extension: &str) -> Option<PathBuf> {
+    let config_dir = drawing
+        .config_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let dir = match &drawing.config.export_dir {
+        Some(dir) => config_dir.join(dir),
+        None => config_dir.to_path_buf(),
+    };
+    match export::export_path(&dir, extension) {
+        Ok(path) => Some(path),
+        Err(error) => {
+            log::error(format_args!(
+                "Failed to create export directory {}: {}",
+                dir.display(),
+                error
+            ));
+            None
+        }
+    }
+}
+
+/// Save the finished strokes as a png, with the background if it is visible
+fn export_png(drawing: &DrawingState) {
+    let image = raster::rasterize(
+        &drawing.strokes,
+        &drawing.rect,
+        exported_background(drawing),
+    );
+    let path = match export_path(drawing, "png") {
+        Some(path) => path,
+        None => return,
+    };
+    match export::write_png(&image, &path) {
+        Ok(()) => log::info(format_args!("Exported drawing to {}", path.display())),
+        Err(error) => log::error(format_args!(
+            "Failed to export {}: {}",
+            path.display(),
+            error
+        )),
+    }
+}
+
+/// Save the finished strokes as a svg, with the background if it is visible
+fn export_svg(drawing: &DrawingState) {
+    let path = match export_path(drawing, "svg") {
+        Some(path) => path,
+        None => return,
+    };
+    match export::write_svg(
+        &drawing.strokes,
+        &drawing.rect,
+        exported_background(drawing),
+        &path,
+    ) {
+        Ok(()) => log::info(format_args!("Exported drawing to {}", path.display())),
+        Err(error) => log::error(format_args!(
+            "Failed to export {}: {}",
+            path.display(),
+            error
+        )),
+    }
+}
+
+/// Save the finished strokes to the session file
+fn save_session(drawing: &DrawingState) {
+    let session = Session::new(
+        &drawing.strokes,
+        &drawing.rect,
+        drawing.is_background_visible,
+    );
+    match session.save(&drawing.session_path) {
+        Ok(()) => log::info(format_args!(
+            "Saved session to {}",
+            drawing.session_path.display()
+        )),
+        Err(error) => log::error(format_args!(
+            "Failed to save session {}: {}",
+            drawing.session_path.display(),
+            error
+        )),
+    }
+}
+
+/// Load a session file, returns its strokes with new ids following the existing ones
+fn load_session(drawing: &mut DrawingState, path: &Path) -> Option<Vec<Stroke>> {
+    let session = match Session::load(path) {
+        Ok(session) => session,
+        Err(error) => {
+            log::error(format_args!(
+                "Failed to load session {}: {}",
+                path.display(),
+                error
+            ));
+            return None;
+        }
+    };
+
+    drawing.is_background_visible = session.is_background_visible;
+    let mut strokes = session.strokes_for_overlay(&drawing.rect);
+    for stroke in &mut strokes {
+        stroke.id = drawing.next_stroke_id;
+        stroke.order = stroke.id;
+        drawing.next_stroke_id += 1;
+    }
+    Some(strokes)
+}
+
+/// Replace the drawing with a session file, as an undo step
+fn open_session(drawing: &mut DrawingState, path: &Path) {
+    if let Some(strokes) = load_session(drawing, path) {
+        drawing.current_stroke = None;
+        let removed = mem::replace(&mut drawing.strokes, strokes.clone());
+        drawing.history.push(
+            Change {
+                removed,
+                added: strokes,
+            },
+            false,
+        );
+        drawing.need_tessellation = true;
+        drawing.need_redraw = true;
+    }
+}
+
+fn quit(drawing: &mut DrawingState, control_flow: &mut ControlFlow) {
+    end_stroke(drawing);
+    if drawing.config.save_session_on_exit {
+        save_session(drawing);
+    }
+
+    // Kept even after a clean exit, in case it was an accidental escape
+    let session = Session::new(
+        &drawing.strokes,
+        &drawing.rect,
+        drawing.is_background_visible,
+    );
+    drawing.autosave.save_now(session);
+
+    unsafe {
+        gl::DeleteProgram(drawing.gl_context.program);
+        gl::DeleteShader(drawing.gl_context.fs);
+        gl::DeleteShader(drawing.gl_context.vs);
+        gl::DeleteBuffers(1, &drawing.gl_context.vbo);
+        gl::DeleteVertexArrays(1, &drawing.gl_context.vao);
+    }
+    *control_flow = ControlFlow::Exit
+}
+
+/// Undo (if any undo steps are available)
+fn undo(drawing: &mut DrawingState) {
+    if drawing.history.undo(&mut drawing.strokes) {
+        drawing.current_stroke = None;
+        drawing.need_tessellation = true;
+        drawing.need_redraw = true;
+    }
+}
+
+/// Redo (if any undone steps are available)
+fn redo(drawing: &mut DrawingState) {
+    if drawing.history.redo(&mut drawing.strokes) {
+        drawing.current_stroke = None;
+        drawing.need_tessellation = true;
+        drawing.need_redraw = true;
+    }
+}
+
+/// Draw with a color of the palette, switching back to the pen
+fn select_brush_color(drawing: &mut DrawingState, index: usize) {
+    if let Some(brush_color) = drawing.config.brush_colors.get(index) {
+        drawing.brush_color_index = index;
+        drawing.line_style.color = color_to_gl(brush_color.color.0);
+        drawing.tool = Tool::Pen;
+        drawing.need_redraw = true;
+    }
+}
+
+/// Draw with a size preset, and its pressure curve and smoothing if it has some
+fn select_brush_size(drawing: &mut DrawingState, index: usize) {
+    if let Some(preset) = drawing.config.brush_sizes.get(index) {
+        drawing.line_style.width = preset.size;
+        apply_brush_preset(drawing, index);
+    }
+}
+
+/// Pressure curve and smoothing of a size preset, keeping the current width
+fn apply_brush_preset(drawing: &mut DrawingState, index: usize) {
+    if let Some(preset) = drawing.config.brush_sizes.get(index) {
+        drawing.brush_size_index = index;
+        drawing.line_style.pressure_curve = preset
+            .pressure_curve
+            .clone()
+            .unwrap_or_else(|| drawing.config.pressure_curve.clone());
+        drawing.line_style.smoothing_range = preset
+            .smoothing_range
+            .unwrap_or(drawing.config.smoothing_range);
+        drawing.line_style.smoothing_intensity = preset
+            .smoothing_intensity
+            .unwrap_or(drawing.config.smoothing_intensity);
+        drawing.need_redraw = true;
+    }
+}
+
+/// Opacity of the strokes drawn with or without the highlighter
+fn brush_opacity(config: &Config, is_highlighter: bool) -> f32 {
+    if is_highlighter {
+        config.highlighter_opacity
+    } else {
+        1.0
+    }
+}
+
+/// Index of the size preset closest to a width
+fn closest_brush_size(brush_sizes: &[BrushSize], width: f32) -> usize {
+    let distance = |preset: &BrushSize| (preset.size - width).abs();
+    (0..brush_sizes.len())
+        .min_by(|a, b| {
+            distance(&brush_sizes[*a])
+                .partial_cmp(&distance(&brush_sizes[*b]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+/// Use a config reloaded while drawing, strokes already drawn keep their style
+fn apply_config(drawing: &mut DrawingState, config: Config) {
+    let (keybindings, keybinding_errors) = Keybindings::new(&config.keybindings);
+    for error in keybinding_errors {
+        log::error(format_args!("{}", error));
+    }
+    drawing.keybindings = keybindings;
+    drawing.history.set_max_steps(config.undo_history_size);
+    drawing.line_style.line_join = config.line_join;
+    drawing.line_style.opacity = brush_opacity(&config, drawing.is_highlighter);
+    drawing
+        .laser
+        .set_fade_duration(Duration::from_millis(config.laser_fade_duration));
+    drawing.config = config;
+
+    // Same palette color and size preset, or the last ones if there are less
+    drawing.brush_color_index = drawing
+        .brush_color_index
+        .min(drawing.config.brush_colors.len() - 1);
+    drawing.line_style.color = color_to_gl(
+        drawing.config.brush_colors[drawing.brush_color_index]
+            .color
+            .0,
+    );
+    // The width may have been changed with the wheel or --size, it is kept
+    let brush_size_index = drawing
+        .brush_size_index
+        .min(drawing.config.brush_sizes.len() - 1);
+    apply_brush_preset(drawing, brush_size_index);
+
+    drawing.need_redraw = true;
+}
+
+fn run_action(drawing: &mut DrawingState, action: Action, control_flow: &mut ControlFlow) {
+    match action {
+        Action::Quit => {
+            // Todo: Request close event
+            quit(drawing, control_flow);
+        }
+        Action::Help => {
+            drawing.need_redraw = true;
+            // TODO: Show help
+        }
+        Action::TogglePressurePreview => {
+            drawing.need_redraw = true;
+            drawing.is_pressure_preview_visible = !drawing.is_pressure_preview_visible;
+        }
+        Action::ToggleHighlighter => {
+            drawing.need_redraw = true;
+            drawing.is_highlighter = !drawing.is_highlighter;
+            drawing.line_style.opacity = brush_opacity(&drawing.config, drawing.is_highlighter);
+            drawing.tool = Tool::Pen;
+        }
+        Action::ToggleAutoFade => {
+            drawing.need_redraw = true;
+            drawing.is_auto_fade = !drawing.is_auto_fade;
+        }
+        Action::ToggleBackground => {
+            drawing.need_redraw = true;
+            drawing.is_background_visible = !drawing.is_background_visible;
+        }
+        Action::ToggleStrokeEraser => {
+            drawing.need_redraw = true;
+            drawing.tool = if drawing.tool == Tool::StrokeEraser {
+                Tool::Pen
+            } else {
+                Tool::StrokeEraser
+            };
+        }
+        Action::TogglePartialEraser => {
+            drawing.need_redraw = true;
+            drawing.tool = if drawing.tool == Tool::PartialEraser {
+                Tool::Pen
+            } else {
+                Tool::PartialEraser
+            };
+        }
+        Action::ToggleLaser => {
+            drawing.need_redraw = true;
+            drawing.tool = if drawing.tool == Tool::Laser {
+                Tool::Pen
+            } else {
+                Tool::Laser
+            };
+        }
+        Action::Shape(kind) => {
+            drawing.need_redraw = true;
+            drawing.tool = if drawing.tool == Tool::Shape(kind) {
+                Tool::Pen
+            } else {
+                Tool::Shape(kind)
+            };
+        }
+        Action::Clear => {
+            // Clear drawings, as an undo step
+            drawing.need_redraw = true;
+            drawing.need_tessellation = true;
+            drawing.current_stroke = None;
+            if !drawing.strokes.is_empty() {
+                let removed = mem::take(&mut drawing.strokes);
+                drawing.history.push(
+                    Change {
+                        removed,
+                        ..Default::default()
+                    },
+                    false,
+                );
+            }
+        }
+        Action::Undo => undo(drawing),
+        Action::Redo => redo(drawing),
+        Action::SaveSession => {
+            end_stroke(drawing);
+            save_session(drawing);
+        }
+        Action::OpenSession => {
+            let path = drawing.session_path.clone();
+            open_session(drawing, &path);
+        }
+        Action::RestoreRecovery => {
+            // Restore the drawing autosaved by the last launch
+            let path = drawing.previous_recovery_path.clone();
+            open_session(drawing, &path);
+        }
+        Action::ExportPng => export_png(drawing),
+        Action::ExportSvg => export_svg(drawing),
+        Action::BrushColor(index) => select_brush_color(drawing, index),
+        Action::NextColor => {
+            let n_colors = drawing.config.brush_colors.len();
+            select_brush_color(drawing, (drawing.brush_color_index + 1) % n_colors);
+        }
+        Action::PreviousColor => {
+            let n_colors = drawing.config.brush_colors.len();
+            select_brush_color(
+                drawing,
+                (drawing.brush_color_index + n_colors - 1) % n_colors,
+            );
+        }
+        Action::BrushSize(index) => select_brush_size(drawing, index),
+        Action::NextSize => {
+            let last = drawing.config.brush_sizes.len() - 1;
+            select_brush_size(drawing, (drawing.brush_size_index + 1).min(last));
+        }
+        Action::PreviousSize => {
+            select_brush_size(drawing, drawing.brush_size_index.saturating_sub(1));
+        }
+    }
+}
+
 fn handle_event(
-    event: Event<()>,
+    event: Event<UserEvent>,
     control_flow: &mut ControlFlow,
     drawing: &mut DrawingState,
     input: &mut Input,
-) -> () {
+) {
     *control_flow = ControlFlow::Wait;
 
     match event {
-        Event::LoopDestroyed => return,
+        Event::LoopDestroyed => (),
+        Event::UserEvent(UserEvent::ConfigChanged) => {
+            if let Some(config) = config::reload_config(&drawing.config_path) {
+                apply_config(drawing, config);
+            }
+        }
         Event::WindowEvent { event, .. } => match event {
             // Alt-tab in and out
             WindowEvent::Focused(has_focus) => {
@@ -478,215 +1075,101 @@ fn handle_event(
                 device_id: _,
                 input: keyboard_input,
                 is_synthetic: _,
-            } => {
-                if keyboard_input.state == glutin::event::ElementState::Released {
-                    match keyboard_input.virtual_keycode {
-                        None => (),
-                        Some(key) => {
-                            match key {
-                                // escape
-                                VirtualKeyCode::Escape => {
-                                    // Todo: Request close event
-                                    unsafe {
-                                        gl::DeleteProgram(drawing.gl_context.program);
-                                        gl::DeleteShader(drawing.gl_context.fs);
-                                        gl::DeleteShader(drawing.gl_context.vs);
-                                        gl::DeleteBuffers(1, &drawing.gl_context.vbo);
-                                        gl::DeleteVertexArrays(1, &drawing.gl_context.vao);
-                                    }
-                                    *control_flow = ControlFlow::Exit
-                                }
-                                VirtualKeyCode::H => {
-                                    drawing.need_redraw = true;
-                                    // TODO: Show help
-                                }
-                                VirtualKeyCode::B => {
-                                    // Toggle background
-                                    drawing.need_redraw = true;
-                                    drawing.is_background_visible = !drawing.is_background_visible;
-                                }
-                                VirtualKeyCode::Space => {
-                                    // Clear drawings
-                                    drawing.need_redraw = true;
-                                    drawing.vertices.clear(); //resize(0, 0.0);
-                                    drawing.undo_steps.clear();
-                                    drawing.n_points_current_line = 0;
-                                    drawing.smooth_index = 0;
-                                }
-                                VirtualKeyCode::Z => {
-                                    // ctrl-z or cmd-z
-                                    if input.modifiers.ctrl || input.modifiers.logo {
-                                        // Undo (if any undo steps are available)
-                                        match drawing.undo_steps.pop() {
-                                            Some(n) => {
-                                                drawing.vertices.resize(n, 0.0);
-                                                drawing.need_redraw = true;
-                                                drawing.n_points_current_line = 0;
-                                                drawing.smooth_index = drawing.vertices.len();
-                                            }
-                                            None => (),
-                                        }
-                                    }
-                                }
-
-                                // q,w,e,r,... for line colors
-
-                                // q (white)
-                                VirtualKeyCode::Q => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[0]);
-                                    drawing.need_redraw = true;
-                                }
-                                // w (black)
-                                VirtualKeyCode::W => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[1]);
-                                    drawing.need_redraw = true;
-                                }
-                                // e (orange)
-                                VirtualKeyCode::E => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[2]);
-                                    drawing.need_redraw = true;
-                                }
-                                // r (pink)
-                                VirtualKeyCode::R => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[3]);
-                                    drawing.need_redraw = true;
-                                }
-                                // t (red)
-                                VirtualKeyCode::T => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[4]);
-                                    drawing.need_redraw = true;
-                                }
-                                // y (green)
-                                VirtualKeyCode::Y => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[5]);
-                                    drawing.need_redraw = true;
-                                }
-                                // u (blue)
-                                VirtualKeyCode::U => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[6]);
-                                    drawing.need_redraw = true;
-                                }
-                                // i (yellow)
-                                VirtualKeyCode::I => {
-                                    drawing.line_style.color =
-                                        color_to_gl(drawing.config.brush_colors[7]);
-                                    drawing.need_redraw = true;
-                                }
-
-                                // 1,2,3,... for size
-                                VirtualKeyCode::Key1 => {
-                                    drawing.line_style.width = drawing.config.brush_sizes[0];
-                                    drawing.need_redraw = true;
-                                }
-                                VirtualKeyCode::Key2 => {
-                                    drawing.line_style.width = drawing.config.brush_sizes[1];
-                                    drawing.need_redraw = true;
-                                }
-                                VirtualKeyCode::Key3 => {
-                                    drawing.line_style.width = drawing.config.brush_sizes[2];
-                                    drawing.need_redraw = true;
-                                }
-                                VirtualKeyCode::Key4 => {
-                                    drawing.line_style.width = drawing.config.brush_sizes[3];
-                                    drawing.need_redraw = true;
-                                }
-                                VirtualKeyCode::Key5 => {
-                                    drawing.line_style.width = drawing.config.brush_sizes[4];
-                                    drawing.need_redraw = true;
-                                }
-
-                                _ => (),
-                            }
-                        }
+            } if keyboard_input.state == ElementState::Released => {
+                if let Some(key) = keyboard_input.virtual_keycode {
+                    let combo = KeyCombo {
+                        key,
+                        ctrl: input.modifiers.ctrl || input.modifiers.logo,
+                        shift: input.modifiers.shift,
+                        alt: input.modifiers.alt,
+                    };
+                    if let Some(action) = drawing.keybindings.action(&combo) {
+                        run_action(drawing, action, control_flow);
                     }
                 }
             }
             WindowEvent::Touch(touch_event) => {
+                // Touches are ignored while another press draws, until it is released
+                let is_new_press =
+                    touch_event.phase == TouchPhase::Started && !input.cursor.pressed;
+                let is_pressed_touch = input.cursor.pressure_device == Some(touch_event.device_id)
+                    && input.cursor.touch_id == Some(touch_event.id);
+                if !is_new_press && !is_pressed_touch {
+                    return;
+                }
                 drawing.need_redraw = true;
 
-                if touch_event.phase == TouchPhase::Started {
+                if is_new_press {
                     input.cursor.pressed = true;
+                    input.cursor.pressed_time = Some(SystemTime::now());
+                    input.cursor.pressure_device = Some(touch_event.device_id);
+                    input.cursor.touch_id = Some(touch_event.id);
                 }
                 if touch_event.phase == TouchPhase::Ended
                     || touch_event.phase == TouchPhase::Cancelled
                 {
-                    input.cursor.pressed = false;
-                    input.cursor.released_time = Some(SystemTime::now());
-
-                    for _ in 0..drawing.line_style.smoothing_intensity {
-                        apply_line_smoothing(
-                            &mut drawing.vertices[drawing.smooth_index..],
-                            drawing.line_style.smoothing_range,
-                        );
-                    }
-                    drawing.smooth_index = drawing.vertices.len();
-
-                    drawing.need_redraw = true;
+                    handle_release(drawing, input);
                 }
 
-                input.cursor.last_x = input.cursor.x;
-                input.cursor.last_y = input.cursor.y;
                 input.cursor.x = touch_event.location.x as f32;
                 input.cursor.y = touch_event.location.y as f32;
 
-                match touch_event.force {
-                    Some(force_type) => match force_type {
-                        glutin::event::Force::Calibrated {
-                            force,
-                            max_possible_force,
-                            altitude_angle: _,
-                        } => {
-                            drawing.line_style.pressure = (force / max_possible_force) as f32;
-                        }
-                        glutin::event::Force::Normalized(force) => {
-                            drawing.line_style.pressure = force as f32;
-                        }
-                    },
-                    None => (),
+                // Touch screens without pressure, or without a maximum force, draw at full width
+                input.cursor.pressure = touch_event.force.map(|force_type| match force_type {
+                    glutin::event::Force::Calibrated {
+                        force,
+                        max_possible_force,
+                        altitude_angle: _,
+                    } if max_possible_force > 0.0 => (force / max_possible_force) as f32,
+                    glutin::event::Force::Calibrated { .. } => 1.0,
+                    glutin::event::Force::Normalized(force) => force as f32,
+                });
+
+                if input.cursor.pressed {
+                    handle_drag(drawing, input);
                 }
             }
             WindowEvent::CloseRequested => {
-                unsafe {
-                    gl::DeleteProgram(drawing.gl_context.program);
-                    gl::DeleteShader(drawing.gl_context.fs);
-                    gl::DeleteShader(drawing.gl_context.vs);
-                    gl::DeleteBuffers(1, &drawing.gl_context.vbo);
-                    gl::DeleteVertexArrays(1, &drawing.gl_context.vao);
-                }
-                *control_flow = ControlFlow::Exit
+                quit(drawing, control_flow);
             }
             // Mouse pressed
+            // Mouse buttons emulated by the system during a touch are ignored
             // deprecated is for modifiers
             #[allow(deprecated)]
             WindowEvent::MouseInput {
                 device_id: _,
                 state,
-                button,
+                button: MouseButton::Left,
                 modifiers: _,
-            } => {
-                if button == MouseButton::Left {
-                    input.cursor.pressed = state == ElementState::Pressed;
-
-                    if input.cursor.pressed == false {
-                        input.cursor.released_time = Some(SystemTime::now());
-
-                        for _ in 0..drawing.line_style.smoothing_intensity {
-                            apply_line_smoothing(
-                                &mut drawing.vertices[drawing.smooth_index..],
-                                drawing.line_style.smoothing_range,
-                            );
-                        }
-                        drawing.smooth_index = drawing.vertices.len();
-
-                        drawing.need_redraw = true;
-                    }
+            } if input.cursor.pressure_device.is_none() => {
+                if state == ElementState::Pressed {
+                    // Mice have no pressure, the last pen pressure is not kept
+                    input.cursor.pressure = None;
+                    input.cursor.pressed = true;
+                    input.cursor.pressed_time = Some(SystemTime::now());
+                    // Starts the stroke, so a click without moving draws a dot
+                    handle_drag(drawing, input);
+                } else {
+                    handle_release(drawing, input);
+                }
+            }
+            // Right click always erases, tablet drivers can map the pen eraser to it
+            // deprecated is for modifiers
+            #[allow(deprecated)]
+            WindowEvent::MouseInput {
+                device_id: _,
+                state,
+                button: MouseButton::Right,
+                modifiers: _,
+            } if input.cursor.pressure_device.is_none() => {
+                if state == ElementState::Pressed {
+                    input.cursor.pressure = None;
+                    input.cursor.pressed = true;
+                    input.cursor.is_eraser_button = true;
+                    input.cursor.pressed_time = Some(SystemTime::now());
+                    handle_drag(drawing, input);
+                } else {
+                    handle_release(drawing, input);
                 }
             }
             // Mousewheel
@@ -694,37 +1177,42 @@ fn handle_event(
             #[allow(deprecated)]
             WindowEvent::MouseWheel {
                 device_id: _,
-                delta,
-                phase,
+                delta: MouseScrollDelta::LineDelta(_x, y),
+                phase: TouchPhase::Moved,
                 modifiers: _,
             } => {
-                if phase == TouchPhase::Moved {
-                    match delta {
-                        MouseScrollDelta::LineDelta(_x, y) => {
-                            drawing.need_redraw = true;
-
-                            drawing.line_style.width -= y;
-                            if drawing.line_style.width < 1.0 {
-                                drawing.line_style.width = 1.0;
-                            }
-                        }
-                        _ => (),
-                    }
+                drawing.need_redraw = true;
+
+                drawing.line_style.width -= y;
+                if drawing.line_style.width < 1.0 {
+                    drawing.line_style.width = 1.0;
                 }
+                // The brush follows the preset closest to the new width, next and
+                // previous sizes continue from it
+                let brush_size_index =
+                    closest_brush_size(&drawing.config.brush_sizes, drawing.line_style.width);
+                apply_brush_preset(drawing, brush_size_index);
             }
             // Mouse moved
             // deprecated is for modifiers
             #[allow(deprecated)]
             WindowEvent::CursorMoved {
-                device_id: _,
+                device_id,
                 position,
                 modifiers: _,
             } => {
-                input.cursor.last_x = input.cursor.x;
-                input.cursor.last_y = input.cursor.y;
                 input.cursor.x = position.x as f32;
                 input.cursor.y = position.y as f32;
                 drawing.need_redraw = true;
+
+                // A touch stroke only takes the samples of its device, which
+                // come with their own pressure
+                let is_touch_device = input.cursor.pressure_device == Some(device_id);
+                if input.cursor.pressed
+                    && (input.cursor.pressure_device.is_none() || is_touch_device)
+                {
+                    handle_drag(drawing, input);
+                }
             }
             _ => (),
         },
@@ -732,17 +1220,26 @@ fn handle_event(
     }
 }
 
-fn redraw(drawing: &mut DrawingState, input: &Input, cursor_vertices: &mut Vec<f32>) {
-    let prev_cursor_gl_pos = Point {
-        x: input.cursor.last_x / drawing.rect.width * 2.0 - 1.0,
-        y: input.cursor.last_y / drawing.rect.height * -2.0 + 1.0,
-        z: 0.0,
-    };
-    let cursor_gl_pos = Point {
-        x: input.cursor.x / drawing.rect.width * 2.0 - 1.0,
-        y: input.cursor.y / drawing.rect.height * -2.0 + 1.0,
-        z: 0.0,
-    };
+/// Stroke from the cursor to the right with the pressure going from 0 to 1,
+/// its edge follows the pressure curve of the brush
+fn pressure_preview_stroke(drawing: &DrawingState, input: &Input) -> Stroke {
+    let mut stroke = Stroke::new(0, drawing.line_style.clone());
+    stroke.samples = (0..=PRESSURE_PREVIEW_SAMPLES)
+        .map(|i| {
+            let pressure = i as f32 / PRESSURE_PREVIEW_SAMPLES as f32;
+            Sample {
+                x: input.cursor.x + drawing.line_style.width + pressure * PRESSURE_PREVIEW_LENGTH,
+                y: input.cursor.y,
+                pressure,
+                time_ms: 0,
+            }
+        })
+        .collect();
+    stroke
+}
+
+fn redraw(drawing: &mut DrawingState, input: &Input, cursor_vertices: &mut [f32]) {
+    let cursor_gl_pos = screen_point_to_gl(input.cursor.x, input.cursor.y, &drawing.rect);
 
     let cursor_gl_size = screen_size_to_gl(
         drawing.line_style.width,
@@ -758,163 +1255,65 @@ fn redraw(drawing: &mut DrawingState, input: &Input, cursor_vertices: &mut Vec<f
     // Cursor circle overlay
     for i in 0..N_CURSOR_RETICLE_POINTS {
         let angle = (i as f32) / (N_CURSOR_RETICLE_POINTS as f32) * (2.0 * PI);
-        cursor_vertices[i * 6 + 0] = cursor_gl_pos.x + (angle.cos() * cursor_gl_size.width);
-        cursor_vertices[i * 6 + 1] = cursor_gl_pos.y + (angle.sin() * cursor_gl_size.height);
-        // skip z  [i * 6 + 2]
-        cursor_vertices[i * 6 + 3] = drawing.line_style.color[0];
-        cursor_vertices[i * 6 + 4] = drawing.line_style.color[1];
-        cursor_vertices[i * 6 + 5] = drawing.line_style.color[2];
+        let vertex = &mut cursor_vertices[i * VERTEX_SIZE..(i + 1) * VERTEX_SIZE];
+        vertex[0] = cursor_gl_pos.x + (angle.cos() * cursor_gl_size.width);
+        vertex[1] = cursor_gl_pos.y + (angle.sin() * cursor_gl_size.height);
+        // skip z  [2]
+        if !is_eraser(active_tool(drawing, input)) {
+            vertex[3..6].copy_from_slice(&drawing.line_style.color);
+            vertex[6] = drawing.line_style.opacity;
+        } else {
+            vertex[3..6].copy_from_slice(&ERASER_RETICLE_COLOR);
+            vertex[6] = 1.0;
+        }
     }
     // // Cursor circle outline
     for i in N_CURSOR_RETICLE_POINTS..(N_CURSOR_RETICLE_POINTS * 2) {
         let angle = (i as f32) / (N_CURSOR_RETICLE_POINTS as f32) * (2.0 * PI);
-        cursor_vertices[i * 6 + 0] = cursor_gl_pos.x + (angle.cos() * cursor_outline_gl_size.width);
-        cursor_vertices[i * 6 + 1] =
-            cursor_gl_pos.y + (angle.sin() * cursor_outline_gl_size.height);
-        // skip z  [i * 6 + 2]
-        cursor_vertices[i * 6 + 3] = 0.0;
-        cursor_vertices[i * 6 + 4] = 0.0;
-        cursor_vertices[i * 6 + 5] = 0.0;
+        let vertex = &mut cursor_vertices[i * VERTEX_SIZE..(i + 1) * VERTEX_SIZE];
+        vertex[0] = cursor_gl_pos.x + (angle.cos() * cursor_outline_gl_size.width);
+        vertex[1] = cursor_gl_pos.y + (angle.sin() * cursor_outline_gl_size.height);
+        // skip z  [2]
+        vertex[3..7].copy_from_slice(&[0.0, 0.0, 0.0, 1.0]);
     }
 
-    if !input.cursor.pressed || drawing.is_window_hidden {
-        drawing.n_points_current_line = 0;
-    } else {
-        /*
-        Each line segment is formed of 2 triangles that form a quad
-
-        p3 __ p4    - old cursor position
-          |\ |
-          | \|
-        p1 ¯¯ p2    - new cursor position
-
-        p1: current cursor position - line width
-        p2: current cursor position + line width
-        p3: previous cursor position - line width
-        p4: previous cursor position + line width
-
-        The cursor position is always between the two points
-        p3 ____ old cursor ____ p4
-          |                    |
-          |                    |
-          |                    |
-          |                    |
-        p1¯¯¯¯¯ new cursor ¯¯¯¯ p2
-        */
-
-        // Angle in radians of the line to draw
-        // Will be wrong if it's the first vertex since prev_positions isn't defined
-        // but we recalculate it before drawing when we get the second vertex
-        let angle =
-            (cursor_gl_pos.y - prev_cursor_gl_pos.y).atan2(cursor_gl_pos.x - prev_cursor_gl_pos.x);
-
-        // New line segment, add an undo point
-        if drawing.n_points_current_line == 0
-            && (input.cursor.released_time.is_none()
-                || input
-                    .cursor
-                    .released_time
-                    .unwrap()
-                    .elapsed()
-                    .unwrap()
-                    .as_millis()
-                    > 200)
-        {
-            drawing.undo_steps.push(drawing.vertices.len());
+    // Finished strokes are only tessellated again when they change
+    if drawing.need_tessellation {
+        drawing.need_tessellation = false;
+        drawing.vertices.clear();
+        for (i, stroke) in drawing.strokes.iter().enumerate() {
+            stroke.tessellate(&drawing.rect, true, stroke_depth(i), &mut drawing.vertices);
         }
+    }
 
-        // update line width in gl scale
-        let line_gl_size = screen_size_to_gl(
-            drawing.line_style.width * drawing.line_style.pressure,
-            drawing.line_style.width * drawing.line_style.pressure,
+    // The stroke being drawn is appended temporarily
+    let n_finished_vertices = drawing.vertices.len();
+    let mut layer = drawing.strokes.len();
+    if let Some(stroke) = &drawing.current_stroke {
+        stroke.tessellate(
             &drawing.rect,
+            false,
+            stroke_depth(layer),
+            &mut drawing.vertices,
+        );
+        layer += 1;
+    }
+    for stroke in drawing.laser.frame_strokes() {
+        stroke.tessellate(
+            &drawing.rect,
+            false,
+            stroke_depth(layer),
+            &mut drawing.vertices,
+        );
+        layer += 1;
+    }
+    if drawing.is_pressure_preview_visible {
+        pressure_preview_stroke(drawing, input).tessellate(
+            &drawing.rect,
+            false,
+            stroke_depth(layer),
+            &mut drawing.vertices,
         );
-
-        // Previous triangles ending points (old p1.x, p1.y, p2.x, p2.y)
-        let mut prev_p1 = Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        };
-        let mut prev_p2 = Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        };
-
-        // If it's the second vertex of the line segment,
-        // we need to recalculate the width of the first vertex since
-        // we didnt know the angle yet
-        if drawing.n_points_current_line == 1 {
-            prev_p1.x = prev_cursor_gl_pos.x + (angle - FRAC_PI_2).cos() * line_gl_size.width;
-            prev_p1.y = prev_cursor_gl_pos.y + (angle - FRAC_PI_2).sin() * line_gl_size.height;
-            prev_p2.x = prev_cursor_gl_pos.x + (angle + FRAC_PI_2).cos() * line_gl_size.width;
-            prev_p2.y = prev_cursor_gl_pos.y + (angle + FRAC_PI_2).sin() * line_gl_size.height;
-        // Get the previous positions from the list of vertices
-        } else if drawing.n_points_current_line > 1 {
-            prev_p1.x = drawing.vertices[drawing.vertices.len() - 24];
-            prev_p1.y = drawing.vertices[drawing.vertices.len() - 23];
-            prev_p2.x = drawing.vertices[drawing.vertices.len() - 12];
-            prev_p2.y = drawing.vertices[drawing.vertices.len() - 11];
-        }
-
-        // point to the left of the cursor
-        let p1 = Point {
-            x: cursor_gl_pos.x + (angle - FRAC_PI_2).cos() * line_gl_size.width,
-            y: cursor_gl_pos.y + (angle - FRAC_PI_2).sin() * line_gl_size.height,
-            z: 0.0,
-        };
-
-        // point to the right of the cursor
-        let p2 = Point {
-            x: cursor_gl_pos.x + (angle + FRAC_PI_2).cos() * line_gl_size.width,
-            y: cursor_gl_pos.y + (angle + FRAC_PI_2).sin() * line_gl_size.height,
-            z: 0.0,
-        };
-
-        // same position as previous p1
-        let p3 = if drawing.n_points_current_line > 0 {
-            prev_p1.clone()
-        } else {
-            // create 0 height rect for first line segment
-            p1.clone()
-        };
-
-        // same position as previous p2
-        let p4 = if drawing.n_points_current_line > 0 {
-            prev_p2.clone()
-        } else {
-            // create 0 height rect for first line segment
-            p2.clone()
-        };
-
-        // Triangle 3-2-1
-        // 3
-        drawing.vertices.extend(&(&p3).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        // 2
-        drawing.vertices.extend(&(&p2).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        // 1
-        drawing.vertices.extend(&(&p1).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        // Triangle 3-2-4
-        // 3
-        drawing.vertices.extend(&(&p3).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        // 2
-        drawing.vertices.extend(&(&p2).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        // 4
-        drawing.vertices.extend(&(&p4).into_array());
-        drawing.vertices.extend(&drawing.line_style.color);
-
-        drawing.n_points_current_line += 1;
     }
 
     if drawing.is_window_hidden {
@@ -938,13 +1337,13 @@ fn redraw(drawing: &mut DrawingState, input: &Input, cursor_vertices: &mut Vec<f
             } else {
                 gl::ClearColor(0.0, 0.0, 0.0, 0.0);
             }
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
             // Draw cursor reticle
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (cursor_vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                mem::transmute(&cursor_vertices[0]),
+                mem::size_of_val(cursor_vertices) as GLsizeiptr,
+                cursor_vertices.as_ptr() as *const GLvoid,
                 gl::STATIC_DRAW,
             );
 
@@ -957,82 +1356,179 @@ fn redraw(drawing: &mut DrawingState, input: &Input, cursor_vertices: &mut Vec<f
                 N_CURSOR_RETICLE_POINTS as i32,
             );
 
-            if drawing.vertices.len() > 0 {
+            if !drawing.vertices.is_empty() {
+                gl::Enable(gl::DEPTH_TEST);
                 // copy the vertices to the vertex buffer
                 gl::BufferData(
                     gl::ARRAY_BUFFER,
                     (drawing.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                    mem::transmute(&drawing.vertices[0]),
+                    drawing.vertices.as_ptr() as *const GLvoid,
                     gl::STATIC_DRAW,
                 );
 
                 // Draw lines using triangles to draw quads
-                // Divide by 6 since each vertex has 3 floats for pos + 3 for color
-                let n_line_vertices = drawing.vertices.len() / 6;
+                let n_line_vertices = drawing.vertices.len() / VERTEX_SIZE;
                 if n_line_vertices > 0 {
                     gl::DrawArrays(gl::TRIANGLES, 0, n_line_vertices as i32);
                 }
+                gl::Disable(gl::DEPTH_TEST);
             }
         }
     }
 
-    drawing.gl_context.window_context.swap_buffers().unwrap();
-}
+    drawing.vertices.truncate(n_finished_vertices);
 
-fn create_default_config_file() -> std::io::Result<String> {
-    let mut f = std::fs::File::create("config.json").expect("Failed to create default config file");
-
-    let default_config_json =
-        serde_json::to_string_pretty(&Config::default()).expect("Failed to encode config");
-
-    f.write_all(default_config_json.as_bytes())
-        .expect("Failed to write config to file");
-
-    Ok(default_config_json)
+    drawing.gl_context.window_context.swap_buffers().unwrap();
 }
 
-fn load_config() -> Config {
-    let config_file_contents = match fs::read_to_string("config.json") {
-        Err(_) => create_default_config_file(),
-        Ok(r) => Ok(r),
-    }
-    .expect("Failed to read from config file");
-
-    serde_json::from_str(&config_file_contents).unwrap()
+/// Depth of the stroke at a position in the drawing order
+///
+/// Later strokes are closer, so they are drawn over the earlier ones, while
+/// the depth test skips the pixels a stroke already covered itself. This
+/// keeps translucent strokes from getting darker where they overlap.
+fn stroke_depth(layer: usize) -> f32 {
+    (1.0 - (layer + 1) as f32 * STROKE_DEPTH_STEP).max(-1.0)
 }
 
 fn color_to_gl(color: [u32; 3]) -> [f32; 3] {
-    return [
+    [
         color[0] as f32 / 255.0,
         color[1] as f32 / 255.0,
         color[2] as f32 / 255.0,
-    ];
+    ]
 }
 
 fn main() {
-    let config = load_config();
-    let event_loop = glutin::event_loop::EventLoop::new();
-    let overlay_rect = get_overlay_rect(event_loop.available_monitors());
-    let mut cursor_vertices = Vec::new(); // List of vertices sent to the vba. Each vertices is x, y, z, r, g, b (6 length)
+    let options = cli::parse_options();
+    let config_path = find_config_file(options.config_path);
+    log::init(&config_path.with_file_name(LOG_FILE));
+    log::info(format_args!("Using config file {}", config_path.display()));
+    let config = load_config(&config_path);
+
+    if let (Some(session_path), Some(export_path)) = (&options.session_path, &options.export_path) {
+        match export_session(
+            &config,
+            session_path,
+            export_path,
+            options.is_background_visible,
+        ) {
+            Ok(()) => return,
+            Err(error) => {
+                log::error(format_args!(
+                    "Failed to export {}: {}",
+                    export_path.display(),
+                    error
+                ));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Session given on the command line is opened on launch
+    let is_session_opened = options.session_path.is_some();
+    // Session file saved and opened with shortcuts, next to the config by default
+    let session_path = options
+        .session_path
+        .unwrap_or_else(|| config_path.with_file_name(DEFAULT_SESSION_FILE));
+
+    // The drawing autosaved by the last launch stays available until the next one
+    // Kept next to the config so they don't depend on where Inke is launched from
+    let recovery_path = config_path.with_file_name(RECOVERY_FILE);
+    let previous_recovery_path = config_path.with_file_name(PREVIOUS_RECOVERY_FILE);
+    let (keybindings, keybinding_errors) = Keybindings::new(&config.keybindings);
+    for error in keybinding_errors {
+        log::error(format_args!("{}", error));
+    }
+    if recovery_path.exists() {
+        match fs::rename(&recovery_path, &previous_recovery_path) {
+            Ok(()) => match keybindings.shortcut(Action::RestoreRecovery) {
+                Some(shortcut) => log::info(format_args!(
+                    "Last drawing can be restored with {}",
+                    shortcut
+                )),
+                None => log::info(format_args!(
+                    "Last drawing can be restored by binding a key to restore_recovery"
+                )),
+            },
+            Err(error) => log::error(format_args!("Failed to keep the last autosave: {}", error)),
+        }
+    }
+
+    let event_loop = glutin::event_loop::EventLoop::with_user_event();
+    let mut overlay_rect = match &options.monitors {
+        Some(indexes) => get_overlay_rect(
+            event_loop
+                .available_monitors()
+                .enumerate()
+                .filter(|(i, _)| indexes.contains(i))
+                .map(|(_, monitor)| monitor),
+        ),
+        None => get_overlay_rect(event_loop.available_monitors()),
+    };
+    if overlay_rect.width <= 0.0 || overlay_rect.height <= 0.0 {
+        log::error(format_args!(
+            "No monitor matches the --monitor options, covering all monitors"
+        ));
+        overlay_rect = get_overlay_rect(event_loop.available_monitors());
+    }
+    let default_brush_color_index = match options.color_index {
+        Some(index) if index < config.brush_colors.len() => index,
+        Some(index) => {
+            log::error(format_args!("No brush color at index {}", index));
+            config.default_brush_color_index as usize
+        }
+        None => config.default_brush_color_index as usize,
+    };
+    let brush_size = options.brush_size.unwrap_or(config.default_brush_size);
+    let mut cursor_vertices = Vec::new(); // List of vertices sent to the vba. Each vertices is x, y, z, r, g, b, a (7 length)
     let mut drawing = DrawingState {
-        need_redraw: true,            // Triggers a screen redraw when set to true
-        is_window_hidden: true,       // Hide the drawing while keeping focus
-        is_background_visible: false, // Toggle background color overlay
-        n_points_current_line: 0,     // Number of points in the current line
-        vertices: Vec::new(), // List of vertices sent to the vba. Each vertices is x, y, z, r, g, b (6 length)
+        need_redraw: true,      // Triggers a screen redraw when set to true
+        is_window_hidden: true, // Hide the drawing while keeping focus
+        is_background_visible: options.is_background_visible, // Toggle background color overlay
+        is_pressure_preview_visible: false, // Show the pressure curve next to the cursor
+        is_highlighter: false,  // Draw translucent strokes
+        is_auto_fade: false,    // New strokes disappear after the auto fade delay
+        need_tessellation: false, // Rebuild the vertices of finished strokes when set to true
+        vertices: Vec::new(), // List of vertices sent to the vba. Each vertices is x, y, z, r, g, b, a (7 length)
         gl_context: init_gl_window(&event_loop, &overlay_rect),
         rect: overlay_rect,
+        brush_color_index: default_brush_color_index, // Palette color of the line style
+        brush_size_index: closest_brush_size(&config.brush_sizes, brush_size), // Size preset stepped from by the next and previous size keys
+        tool: Tool::Pen,           // Action of the cursor when pressed
+        eraser: Eraser::default(), // Strokes removed by the current eraser drag
         line_style: LineStyle {
-            color: color_to_gl(config.brush_colors[config.default_brush_color_index as usize]), // rgb of the line to draw. Also used by the cursor reticle
-            width: config.default_brush_size, // Line width to draw *in pixels*
-            pressure: 1.0,                    // Used by pen pressure to change the width
+            color: color_to_gl(config.brush_colors[default_brush_color_index].color.0), // rgb of the line to draw. Also used by the cursor reticle
+            width: brush_size, // Line width to draw *in pixels*
             smoothing_range: config.smoothing_range,
             smoothing_intensity: config.smoothing_intensity,
+            pressure_curve: config.pressure_curve.clone(), // Width response to the pen pressure
+            line_join: config.line_join,                   // Shape of the corners of the line
+            opacity: 1.0,                                  // Below 1 with the highlighter
         },
-        undo_steps: Vec::new(), // List of indexes in vertex_data representing each possible undo steps
-        smooth_index: 0,
-        config: config,
+        strokes: Vec::new(),             // Finished strokes, in drawing order
+        current_stroke: None,            // Stroke being drawn while the cursor is pressed
+        is_current_stroke_merged: false, // Current stroke shares the undo step of the previous one
+        next_stroke_id: 0,               // Stable id given to the next stroke
+        history: History::new(config.undo_history_size), // Undo and redo steps
+        session_path,
+        previous_recovery_path, // Drawing autosaved by the last launch
+        autosave: Autosave::new(recovery_path, Duration::from_secs(config.autosave_interval)), // Periodic snapshots of the drawing to recover it after a crash
+        laser: Laser::new(Duration::from_millis(config.laser_fade_duration)), // Fading strokes of the laser pointer
+        keybindings, // Actions of the shortcuts
+        config,
+        config_path,
     };
+    // Same pressure curve and smoothing as after choosing the closest preset, keeping the width
+    let brush_size_index = drawing.brush_size_index;
+    apply_brush_preset(&mut drawing, brush_size_index);
+
+    if is_session_opened {
+        let path = drawing.session_path.clone();
+        if let Some(strokes) = load_session(&mut drawing, &path) {
+            drawing.strokes = strokes;
+            drawing.need_tessellation = true;
+        }
+    }
 
     // Initialize cursor reticle vertices
     // Position will be updated during event loop
@@ -1043,15 +1539,68 @@ fn main() {
         cursor_vertices.push(0.0);
         // color
         cursor_vertices.extend(&drawing.line_style.color);
+        cursor_vertices.push(1.0);
     }
     let mut input: Input = Default::default();
 
+    let event_loop_proxy = event_loop.create_proxy();
+    config::watch_config_file(drawing.config_path.clone(), move || {
+        let _ = event_loop_proxy.send_event(UserEvent::ConfigChanged);
+    });
+
     event_loop.run(move |event, _, control_flow| {
         handle_event(event, control_flow, &mut drawing, &mut input);
 
+        // Quitting deleted the gl objects, nothing can be drawn anymore
+        if *control_flow == ControlFlow::Exit {
+            return;
+        }
+
+        if let Some(expiry) = next_expiry(&drawing) {
+            if Instant::now() >= expiry {
+                expire_strokes(&mut drawing);
+            }
+        }
+
+        if drawing.need_tessellation {
+            drawing.autosave.mark_changed();
+        }
+
+        // Fading laser trails are redrawn even without events
+        if let Some(next_frame) = drawing.laser.next_frame() {
+            if Instant::now() >= next_frame {
+                drawing.need_redraw = true;
+            }
+        }
+
         if drawing.need_redraw {
             drawing.need_redraw = false;
             redraw(&mut drawing, &input, &mut cursor_vertices);
         }
+
+        if drawing.autosave.is_due() {
+            let session = Session::new(
+                &drawing.strokes,
+                &drawing.rect,
+                drawing.is_background_visible,
+            );
+            drawing.autosave.save(session);
+        }
+
+        // Wake up for the next autosave, laser frame or stroke expiry even if there are no events
+        if *control_flow == ControlFlow::Wait {
+            let deadline = [
+                drawing.autosave.deadline(),
+                drawing.laser.next_frame(),
+                next_expiry(&drawing),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+            if let Some(deadline) = deadline {
+                *control_flow = ControlFlow::WaitUntil(deadline);
+            }
+        }
     });
 }
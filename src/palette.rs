@@ -0,0 +1,165 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::stroke::PressureCurve;
+
+/// Rgb color, 0 to 255 per channel
+///
+/// Written as `#ff9600` in the config, also read as `[255, 150, 0]`.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(try_from = "ColorValue", into = "ColorValue")]
+pub struct Color(pub [u32; 3]);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgb([u32; 3]),
+    Hex(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgb(rgb) => Ok(Color(rgb)),
+            ColorValue::Hex(hex) => parse_hex(&hex)
+                .map(Color)
+                .ok_or_else(|| format!("invalid color '{}', expected #rrggbb", hex)),
+        }
+    }
+}
+
+impl From<Color> for ColorValue {
+    fn from(color: Color) -> Self {
+        let [r, g, b] = color.0;
+        ColorValue::Hex(format!("#{:02x}{:02x}{:02x}", r, g, b))
+    }
+}
+
+/// Parse `#rrggbb`, the `#` is optional
+fn parse_hex(hex: &str) -> Option<[u32; 3]> {
+    let digits = hex.trim().trim_start_matches('#');
+    if digits.len() != 6 || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u32::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Color of the palette, with an optional name
+///
+/// A bare color such as `"#ff9600"` or `[255, 150, 0]` is also accepted,
+/// as written by older configs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "Value")]
+pub struct BrushColor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub color: Color,
+}
+
+#[derive(Deserialize)]
+struct NamedColor {
+    name: Option<String>,
+    color: Color,
+}
+
+// Read through a json value instead of an untagged enum to keep the error
+// of the matching form
+impl TryFrom<Value> for BrushColor {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if value.is_object() {
+            let NamedColor { name, color } = serde_json::from_value(value)?;
+            Ok(BrushColor { name, color })
+        } else {
+            let color = serde_json::from_value(value)?;
+            Ok(BrushColor { name: None, color })
+        }
+    }
+}
+
+pub fn default_palette() -> Vec<BrushColor> {
+    [
+        ("white", [255, 255, 255]),
+        ("black", [10, 10, 10]),
+        ("orange", [255, 150, 0]),
+        ("pink", [255, 0, 220]),
+        ("red", [255, 50, 50]),
+        ("green", [25, 255, 75]),
+        ("blue", [25, 75, 255]),
+        ("yellow", [255, 255, 0]),
+    ]
+    .iter()
+    .map(|(name, rgb)| BrushColor {
+        name: Some(name.to_string()),
+        color: Color(*rgb),
+    })
+    .collect()
+}
+
+/// Brush size preset, in pixels
+///
+/// A preset can draw with its own pressure curve and smoothing instead of
+/// the ones of the config. A bare number is also accepted, as written by
+/// older configs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "Value")]
+pub struct BrushSize {
+    pub size: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure_curve: Option<PressureCurve>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothing_range: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothing_intensity: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct Preset {
+    size: f32,
+    pressure_curve: Option<PressureCurve>,
+    smoothing_range: Option<usize>,
+    smoothing_intensity: Option<usize>,
+}
+
+impl TryFrom<Value> for BrushSize {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if value.is_object() {
+            let preset: Preset = serde_json::from_value(value)?;
+            Ok(BrushSize {
+                size: preset.size,
+                pressure_curve: preset.pressure_curve,
+                smoothing_range: preset.smoothing_range,
+                smoothing_intensity: preset.smoothing_intensity,
+            })
+        } else {
+            Ok(BrushSize::new(serde_json::from_value(value)?))
+        }
+    }
+}
+
+impl BrushSize {
+    /// Preset drawing with the pressure curve and smoothing of the config
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            pressure_curve: None,
+            smoothing_range: None,
+            smoothing_intensity: None,
+        }
+    }
+}
+
+pub fn default_brush_sizes() -> Vec<BrushSize> {
+    [1.0, 3.0, 5.0, 10.0, 30.0]
+        .iter()
+        .map(|size| BrushSize::new(*size))
+        .collect()
+}
@@ -0,0 +1,235 @@
+use crate::stroke::Stroke;
+use crate::tessellation::Triangle;
+use crate::Rect2D;
+
+/// Coverage samples per pixel side, each pixel is tested at 4x4 positions
+const SUBSAMPLES: usize = 4;
+
+/// RGBA image, 8 bits per channel, not premultiplied
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, fill: [f32; 4]) -> Self {
+        let fill: Vec<u8> = fill.iter().map(|c| channel_to_u8(*c)).collect();
+        Self {
+            width,
+            height,
+            pixels: fill.repeat(width * height),
+        }
+    }
+
+    /// Blend a color over a pixel ("over" operator)
+    fn blend(&mut self, x: usize, y: usize, color: [f32; 4]) {
+        let i = (y * self.width + x) * 4;
+        let dst = &mut self.pixels[i..i + 4];
+        let dst_alpha = dst[3] as f32 / 255.0;
+        let out_alpha = color[3] + dst_alpha * (1.0 - color[3]);
+        if out_alpha <= 0.0 {
+            return;
+        }
+        for c in 0..3 {
+            let dst_color = dst[c] as f32 / 255.0;
+            let out_color =
+                (color[c] * color[3] + dst_color * dst_alpha * (1.0 - color[3])) / out_alpha;
+            dst[c] = channel_to_u8(out_color);
+        }
+        dst[3] = channel_to_u8(out_alpha);
+    }
+}
+
+/// Draw the strokes on the cpu, at the overlay resolution
+///
+/// Uses the same triangles as the gpu. The coverage of each stroke is
+/// computed as a whole before blending it, so the triangles of a stroke
+/// don't show seams where they meet.
+pub fn rasterize(strokes: &[Stroke], overlay_rect: &Rect2D, background: Option<[f32; 4]>) -> Image {
+    let mut image = Image::new(
+        overlay_rect.width as usize,
+        overlay_rect.height as usize,
+        background.unwrap_or([0.0, 0.0, 0.0, 0.0]),
+    );
+
+    for stroke in strokes {
+        let triangles = stroke.triangles(true);
+        let color = stroke.style.color;
+        fill_triangles(
+            &mut image,
+            &triangles,
+            [color[0], color[1], color[2], stroke.style.opacity],
+        );
+    }
+
+    image
+}
+
+/// Blend the union of the triangles over the image
+fn fill_triangles(image: &mut Image, triangles: &[Triangle], color: [f32; 4]) {
+    // Bounding box of the triangles, clipped to the image
+    let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+    let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+    for point in triangles.iter().flatten() {
+        min_x = min_x.min(point.0);
+        min_y = min_y.min(point.1);
+        max_x = max_x.max(point.0);
+        max_y = max_y.max(point.1);
+    }
+    if triangles.is_empty() || max_x < 0.0 || max_y < 0.0 {
+        return;
+    }
+    let left = min_x.max(0.0) as usize;
+    let top = min_y.max(0.0) as usize;
+    let right = (max_x.ceil() as usize).min(image.width);
+    let bottom = (max_y.ceil() as usize).min(image.height);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    // One bit per subsample of each pixel in the bounding box
+    let box_width = right - left;
+    let mut coverage = vec![0u16; box_width * (bottom - top)];
+
+    for triangle in triangles {
+        let [a, b, c] = *triangle;
+        let area = edge(a, b, c);
+        if area == 0.0 {
+            continue;
+        }
+
+        let tri_left = (a.0.min(b.0).min(c.0).max(left as f32) as usize).max(left);
+        let tri_top = (a.1.min(b.1).min(c.1).max(top as f32) as usize).max(top);
+        let tri_right = (a.0.max(b.0).max(c.0).ceil().max(0.0) as usize).min(right);
+        let tri_bottom = (a.1.max(b.1).max(c.1).ceil().max(0.0) as usize).min(bottom);
+
+        for y in tri_top..tri_bottom {
+            for x in tri_left..tri_right {
+                let mut mask = 0u16;
+                for sy in 0..SUBSAMPLES {
+                    for sx in 0..SUBSAMPLES {
+                        let p = (
+                            x as f32 + (sx as f32 + 0.5) / SUBSAMPLES as f32,
+                            y as f32 + (sy as f32 + 0.5) / SUBSAMPLES as f32,
+                        );
+                        // Same sign as the triangle area on all edges means inside
+                        let w0 = edge(b, c, p) * area;
+                        let w1 = edge(c, a, p) * area;
+                        let w2 = edge(a, b, p) * area;
+                        if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                            mask |= 1 << (sy * SUBSAMPLES + sx);
+                        }
+                    }
+                }
+                coverage[(y - top) * box_width + (x - left)] |= mask;
+            }
+        }
+    }
+
+    for y in top..bottom {
+        for x in left..right {
+            let mask = coverage[(y - top) * box_width + (x - left)];
+            if mask != 0 {
+                let alpha = mask.count_ones() as f32 / (SUBSAMPLES * SUBSAMPLES) as f32;
+                image.blend(x, y, [color[0], color[1], color[2], color[3] * alpha]);
+            }
+        }
+    }
+}
+
+/// Signed area of the parallelogram formed by a-b and a-p
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+fn channel_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stroke::{LineStyle, Sample};
+
+    fn pixel(image: &Image, x: usize, y: usize) -> &[u8] {
+        let i = (y * image.width + x) * 4;
+        &image.pixels[i..i + 4]
+    }
+
+    fn overlay_rect(width: f32, height: f32) -> Rect2D {
+        Rect2D {
+            width,
+            height,
+            ..Rect2D::default()
+        }
+    }
+
+    #[test]
+    fn triangle_coverage() {
+        let mut image = Image::new(4, 4, [0.0, 0.0, 0.0, 0.0]);
+        fill_triangles(
+            &mut image,
+            &[[(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)]],
+            [1.0, 0.0, 0.0, 1.0],
+        );
+
+        for y in 0..4 {
+            for x in 0..4 {
+                // Pixels on the diagonal have 10 of their 16 subsamples inside
+                let expected_alpha = match x + y {
+                    0..=2 => 255,
+                    3 => 159,
+                    _ => 0,
+                };
+                assert_eq!(pixel(&image, x, y)[3], expected_alpha, "pixel {} {}", x, y);
+                if expected_alpha > 0 {
+                    assert_eq!(pixel(&image, x, y)[..3], [255, 0, 0]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn background_fill() {
+        let image = rasterize(&[], &overlay_rect(3.0, 2.0), Some([0.2, 0.4, 0.6, 0.8]));
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, [51, 102, 153, 204].repeat(6));
+
+        let image = rasterize(&[], &overlay_rect(3.0, 2.0), None);
+        assert_eq!(image.pixels, [0; 4].repeat(6));
+    }
+
+    #[test]
+    fn self_overlapping_stroke_blends_once() {
+        let mut stroke = Stroke::new(
+            0,
+            LineStyle {
+                color: [1.0, 1.0, 1.0],
+                width: 3.0,
+                opacity: 0.5,
+                ..LineStyle::default()
+            },
+        );
+        // Goes back over itself, then crosses its first part
+        stroke.samples = [
+            (5.0, 10.0),
+            (15.0, 10.0),
+            (5.0, 10.0),
+            (10.0, 5.0),
+            (10.0, 15.0),
+        ]
+        .iter()
+        .map(|&(x, y)| Sample {
+            x,
+            y,
+            pressure: 1.0,
+            time_ms: 0,
+        })
+        .collect();
+
+        let image = rasterize(&[stroke], &overlay_rect(20.0, 20.0), None);
+        assert_eq!(pixel(&image, 10, 10), [255, 255, 255, 128]);
+        assert_eq!(pixel(&image, 7, 10), [255, 255, 255, 128]);
+    }
+}
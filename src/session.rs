@@ -0,0 +1,83 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stroke::Stroke;
+use crate::Rect2D;
+
+/// Version of the session file format, increased on incompatible changes
+pub const SESSION_VERSION: u8 = 1;
+
+/// Drawing saved to disk
+///
+/// Positions are in screen pixels so strokes stay in place when the overlay
+/// covers a different set of monitors.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    pub session_version: u8,
+    pub overlay_rect: Rect2D,
+    pub is_background_visible: bool,
+    pub strokes: Vec<Stroke>,
+}
+
+impl Session {
+    /// Snapshot of the strokes drawn on an overlay
+    pub fn new(strokes: &[Stroke], overlay_rect: &Rect2D, is_background_visible: bool) -> Self {
+        let mut strokes = strokes.to_vec();
+        translate_strokes(&mut strokes, overlay_rect.x, overlay_rect.y);
+
+        Self {
+            session_version: SESSION_VERSION,
+            overlay_rect: overlay_rect.clone(),
+            is_background_visible,
+            strokes,
+        }
+    }
+
+    /// Strokes positioned relative to an overlay
+    pub fn strokes_for_overlay(&self, overlay_rect: &Rect2D) -> Vec<Stroke> {
+        let mut strokes = self.strokes.clone();
+        translate_strokes(&mut strokes, -overlay_rect.x, -overlay_rect.y);
+        strokes
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let session: Session = serde_json::from_str(&contents)?;
+
+        if session.session_version > SESSION_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "session version {} is newer than the supported version {}",
+                    session.session_version, SESSION_VERSION
+                ),
+            ));
+        }
+
+        Ok(session)
+    }
+
+    /// Write the session through a temporary file so an interrupted save
+    /// never leaves a truncated session behind
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+fn translate_strokes(strokes: &mut [Stroke], dx: f32, dy: f32) {
+    for stroke in strokes.iter_mut() {
+        for sample in &mut stroke.samples {
+            sample.x += dx;
+            sample.y += dy;
+        }
+        if let Some(shape) = stroke.shape.as_mut() {
+            shape.translate(dx, dy);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 #version 150
 out vec4 out_color;
-in vec3 fColor;
+in vec4 fColor;
 
 void main() {
-    out_color = vec4(fColor.r, fColor.g, fColor.b, 1.0);
+    out_color = fColor;
 }
\ No newline at end of file
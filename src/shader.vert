@@ -1,7 +1,7 @@
 #version 150
 in vec3 position;
-in vec3 vColor;
-out vec3 fColor;
+in vec4 vColor;
+out vec4 fColor;
 
 void main() {
     fColor = vColor;
@@ -0,0 +1,160 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stroke::Sample;
+
+/// Kind of shape drawn from a press to a release
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapeKind {
+    Line,
+    Arrow,
+    Rectangle,
+    Ellipse,
+}
+
+/// Head at the end of arrows
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(default)]
+pub struct ArrowHead {
+    pub length: f32, // Length of the sides, in pixels
+    pub angle: f32,  // Angle between the sides and the line, in degrees
+}
+
+impl Default for ArrowHead {
+    fn default() -> Self {
+        Self {
+            length: 20.0,
+            angle: 30.0,
+        }
+    }
+}
+
+impl ArrowHead {
+    /// Head drawn on a line of a width, never hidden by the line itself
+    pub fn for_width(&self, width: f32) -> Self {
+        Self {
+            length: self.length.max(width * 3.0),
+            angle: self.angle,
+        }
+    }
+
+    /// Error message if a setting is out of range
+    pub fn validate(&self) -> Result<(), String> {
+        if self.length <= 0.0 {
+            Err("length must be positive".to_string())
+        } else if self.angle <= 0.0 || self.angle >= 90.0 {
+            Err("angle must be between 0 and 90".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Shape stretched between the press and the release positions, in overlay pixels
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrow_head: Option<ArrowHead>, // Only for arrows
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind, position: (f32, f32), arrow_head: ArrowHead) -> Self {
+        Self {
+            kind,
+            start: position,
+            end: position,
+            arrow_head: if kind == ShapeKind::Arrow {
+                Some(arrow_head)
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.start = (self.start.0 + dx, self.start.1 + dy);
+        self.end = (self.end.0 + dx, self.end.1 + dy);
+    }
+
+    /// Sides of the arrow head, from the end of the line
+    pub fn arrow_head_sides(&self) -> Option<((f32, f32), (f32, f32))> {
+        let head = self.arrow_head?;
+        let (dx, dy) = (self.start.0 - self.end.0, self.start.1 - self.end.1);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            return None;
+        }
+
+        let back_angle = dy.atan2(dx);
+        let side = |angle: f32| {
+            (
+                self.end.0 + angle.cos() * head.length,
+                self.end.1 + angle.sin() * head.length,
+            )
+        };
+        let spread = head.angle.to_radians();
+        Some((side(back_angle + spread), side(back_angle - spread)))
+    }
+
+    /// Top left corner and size of the box between the start and the end
+    pub fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        let min = (self.start.0.min(self.end.0), self.start.1.min(self.end.1));
+        let max = (self.start.0.max(self.end.0), self.start.1.max(self.end.1));
+        (min, (max.0 - min.0, max.1 - min.1))
+    }
+
+    /// Points of the outline, drawn as the samples of a stroke
+    ///
+    /// Closed outlines go past their first corner again so it gets a join
+    /// like the others instead of two caps.
+    pub fn samples(&self) -> Vec<Sample> {
+        let points = match self.kind {
+            ShapeKind::Line => vec![self.start, self.end],
+            ShapeKind::Arrow => match self.arrow_head_sides() {
+                Some((left, right)) => vec![self.start, self.end, left, self.end, right],
+                None => vec![self.start, self.end],
+            },
+            ShapeKind::Rectangle => {
+                let ((x, y), (width, height)) = self.bounds();
+                vec![
+                    (x, y),
+                    (x + width, y),
+                    (x + width, y + height),
+                    (x, y + height),
+                    (x, y),
+                    (x + width, y),
+                ]
+            }
+            ShapeKind::Ellipse => {
+                let ((x, y), (width, height)) = self.bounds();
+                let (rx, ry) = (width / 2.0, height / 2.0);
+                let center = (x + rx, y + ry);
+
+                // About one point every 4 pixels of perimeter
+                let perimeter = 2.0 * PI * ((rx * rx + ry * ry) / 2.0).sqrt();
+                let n_points = (perimeter / 4.0).clamp(16.0, 256.0) as usize;
+                (0..=n_points + 1)
+                    .map(|i| {
+                        let angle = i as f32 / n_points as f32 * 2.0 * PI;
+                        (center.0 + angle.cos() * rx, center.1 + angle.sin() * ry)
+                    })
+                    .collect()
+            }
+        };
+
+        points
+            .into_iter()
+            .map(|(x, y)| Sample {
+                x,
+                y,
+                pressure: 1.0,
+                time_ms: 0,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::shape::Shape;
+use crate::tessellation::{self, LineJoin, Triangle};
+use crate::{screen_point_to_gl, Point, Rect2D};
+
+/// Number of floats per vertex sent to the vbo (x, y, z, r, g, b, a)
+pub const VERTEX_SIZE: usize = 7;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct LineStyle {
+    pub color: [f32; 3],
+    pub width: f32,
+    pub smoothing_range: usize,
+    pub smoothing_intensity: usize,
+    #[serde(default)]
+    pub pressure_curve: PressureCurve,
+    #[serde(default)]
+    pub line_join: LineJoin,
+    #[serde(default = "full_opacity")]
+    pub opacity: f32, // Below 1 for the highlighter
+}
+
+fn full_opacity() -> f32 {
+    1.0
+}
+
+impl LineStyle {
+    /// Half of the line width drawn at a pen pressure, in pixels
+    pub fn half_width(&self, pressure: f32) -> f32 {
+        self.width * self.pressure_curve.apply(pressure)
+    }
+}
+
+/// Response of the line width to the pen pressure
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PressureCurve {
+    pub min_width: f32,  // Fraction of the width drawn at the lightest pressure
+    pub gamma: f32,      // Above 1 light pressures draw thinner, below 1 thicker
+    pub saturation: f32, // Pressure drawing the full width, for pens that never reach 1
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<[f32; 2]>, // [pressure, width] pairs replacing the gamma, from 0 to 1
+}
+
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self {
+            min_width: 0.0,
+            gamma: 1.0,
+            saturation: 1.0,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl PressureCurve {
+    /// Fraction of the line width for a pressure between 0 and 1
+    pub fn apply(&self, pressure: f32) -> f32 {
+        // Invalid pressures from the device draw at full width
+        let pressure = if pressure.is_nan() { 1.0 } else { pressure };
+        let pressure = (pressure / self.saturation).clamp(0.0, 1.0);
+        let response = if self.points.is_empty() {
+            pressure.powf(self.gamma)
+        } else {
+            interpolate_points(&self.points, pressure)
+        };
+        self.min_width + (1.0 - self.min_width) * response
+    }
+
+    /// Error message if a setting is out of range
+    pub fn validate(&self) -> Result<(), String> {
+        let is_fraction = |value: f32| (0.0..=1.0).contains(&value);
+        if !is_fraction(self.min_width) {
+            Err("min_width must be between 0 and 1".to_string())
+        } else if self.gamma <= 0.0 {
+            Err("gamma must be positive".to_string())
+        } else if self.saturation <= 0.0 || self.saturation > 1.0 {
+            Err("saturation must be above 0 and up to 1".to_string())
+        } else if !self
+            .points
+            .iter()
+            .flatten()
+            .all(|value| is_fraction(*value))
+        {
+            Err("points must be between 0 and 1".to_string())
+        } else if self.points.windows(2).any(|pair| pair[0][0] > pair[1][0]) {
+            Err("points must be sorted by pressure".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Piecewise linear curve through points sorted by x, flat past its ends
+fn interpolate_points(points: &[[f32; 2]], x: f32) -> f32 {
+    let first = points[0];
+    let last = points[points.len() - 1];
+    if x <= first[0] {
+        return first[1];
+    }
+    if x >= last[0] {
+        return last[1];
+    }
+
+    for pair in points.windows(2) {
+        let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+        if x <= x1 {
+            if x1 - x0 <= 0.0 {
+                return y1;
+            }
+            return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+        }
+    }
+    last[1]
+}
+
+/// Raw input sample, in overlay pixels
+#[derive(Serialize, Deserialize, Default, Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+    pub time_ms: u32, // Milliseconds since the start of the stroke
+}
+
+/// A single continuous line drawn by the user
+///
+/// Only the raw input samples are kept, the triangles sent to the gpu are
+/// always derived from them with `tessellate`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Stroke {
+    pub id: u64,
+    #[serde(skip)]
+    pub order: u64, // Drawing order, shared by the pieces cut out of a stroke
+    pub style: LineStyle,
+    pub samples: Vec<Sample>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<Shape>, // Drawn with a shape tool, the samples follow its outline
+    #[serde(skip)]
+    pub expires_at: Option<Instant>, // Auto-fading strokes disappear at this time
+}
+
+impl Stroke {
+    pub fn new(id: u64, style: LineStyle) -> Self {
+        Self {
+            id,
+            order: id,
+            style,
+            samples: Vec::new(),
+            shape: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now >= expires_at,
+            None => false,
+        }
+    }
+
+    /// Samples with their positions and pressures averaged with their neighbours
+    ///
+    /// The first and last samples stay in place.
+    ///
+    /// Reference: https://stackoverflow.com/a/18830268
+    pub fn smoothed_samples(&self) -> Vec<Sample> {
+        let mut samples = self.samples.clone();
+        let range = self.style.smoothing_range;
+        if range == 0 || samples.len() < 3 {
+            return samples;
+        }
+
+        for _ in 0..self.style.smoothing_intensity {
+            let previous = samples.clone();
+            let last = previous.len() - 1;
+            for (i, sample) in samples.iter_mut().enumerate().take(last).skip(1) {
+                let window = &previous[i.saturating_sub(range)..=(i + range).min(last)];
+                sample.x = window.iter().map(|s| s.x).sum::<f32>() / window.len() as f32;
+                sample.y = window.iter().map(|s| s.y).sum::<f32>() / window.len() as f32;
+                sample.pressure =
+                    window.iter().map(|s| s.pressure).sum::<f32>() / window.len() as f32;
+            }
+        }
+        samples
+    }
+
+    /// Triangles of the stroke, in overlay pixels
+    ///
+    /// Smoothing is only applied when `smooth` is set since a stroke that is
+    /// still being drawn would otherwise move under the cursor.
+    pub fn triangles(&self, smooth: bool) -> Vec<Triangle> {
+        if smooth {
+            tessellation::tessellate(&self.smoothed_samples(), &self.style)
+        } else {
+            tessellation::tessellate(&self.samples, &self.style)
+        }
+    }
+
+    /// Append the triangles of the stroke to a list of vertices (x, y, z, r, g, b, a)
+    ///
+    /// All the vertices are at the `depth` of the stroke, so that with the
+    /// depth test its overlapping triangles only blend once.
+    pub fn tessellate(
+        &self,
+        overlay_rect: &Rect2D,
+        smooth: bool,
+        depth: f32,
+        vertices: &mut Vec<f32>,
+    ) {
+        for triangle in self.triangles(smooth) {
+            for (x, y) in &triangle {
+                let point = Point {
+                    z: depth,
+                    ..screen_point_to_gl(*x, *y, overlay_rect)
+                };
+                vertices.extend(&point.into_array());
+                vertices.extend(&self.style.color);
+                vertices.push(self.style.opacity);
+            }
+        }
+    }
+}
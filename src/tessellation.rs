@@ -0,0 +1,333 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stroke::{LineStyle, Sample};
+
+/// Triangle in overlay pixels
+pub type Triangle = [(f32, f32); 3];
+
+/// Shape of the outer corner where two segments of a stroke meet
+#[derive(Serialize, Deserialize, Default, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineJoin {
+    #[default]
+    Round,
+    Miter,
+    Bevel,
+}
+
+/// Miters reaching further than this many half widths are beveled instead
+const MITER_LIMIT: f32 = 4.0;
+
+/// Samples closer than this to the previous one are skipped, in pixels
+pub const MIN_SEGMENT_LENGTH: f32 = 0.01;
+
+/// Sample position with the half width drawn there
+#[derive(Copy, Clone)]
+struct Node {
+    position: (f32, f32),
+    half_width: f32,
+}
+
+/// Triangles covering a line through samples, in overlay pixels
+///
+/// Each segment is a quad following the widths at its ends. Segments are
+/// joined on the outer side of each turn with `style.line_join`, and both
+/// ends of the line get a round cap. A line that never moves is a dot.
+pub fn tessellate(samples: &[Sample], style: &LineStyle) -> Vec<Triangle> {
+    let nodes = distinct_nodes(samples, style);
+    let mut triangles = Vec::new();
+    match nodes.as_slice() {
+        [] => return triangles,
+        [dot] => {
+            push_arc(&mut triangles, dot.position, dot.half_width, 0.0, 2.0 * PI);
+            return triangles;
+        }
+        _ => (),
+    }
+
+    /*
+    a+ ______ b+    - a and b are the ends of the segment,
+      |\     |        + and - are offset by the half width
+      |  \   |        along the normal of the segment
+      |    \ |
+    a- ¯¯¯¯¯¯ b-
+    */
+    for pair in nodes.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let normal = normal(a.position, b.position);
+        let a_left = offset(a.position, normal, a.half_width);
+        let a_right = offset(a.position, normal, -a.half_width);
+        let b_left = offset(b.position, normal, b.half_width);
+        let b_right = offset(b.position, normal, -b.half_width);
+        triangles.push([a_left, a_right, b_left]);
+        triangles.push([a_right, b_right, b_left]);
+    }
+
+    for corner in nodes.windows(3) {
+        push_join(&mut triangles, corner, style.line_join);
+    }
+
+    // Half circles starting on the normal and turning away from the line
+    let (first, second) = (nodes[0], nodes[1]);
+    let first_normal = normal(first.position, second.position);
+    push_arc(
+        &mut triangles,
+        first.position,
+        first.half_width,
+        angle(first_normal),
+        PI,
+    );
+    let (before_last, last) = (nodes[nodes.len() - 2], nodes[nodes.len() - 1]);
+    let last_normal = normal(before_last.position, last.position);
+    push_arc(
+        &mut triangles,
+        last.position,
+        last.half_width,
+        angle(last_normal),
+        -PI,
+    );
+
+    triangles
+}
+
+/// Samples with their half width, without the ones that don't move
+fn distinct_nodes(samples: &[Sample], style: &LineStyle) -> Vec<Node> {
+    let mut nodes: Vec<Node> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        let position = (sample.x, sample.y);
+        if let Some(previous) = nodes.last() {
+            if length(sub(position, previous.position)) < MIN_SEGMENT_LENGTH {
+                continue;
+            }
+        }
+        nodes.push(Node {
+            position,
+            half_width: style.half_width(sample.pressure),
+        });
+    }
+    nodes
+}
+
+/// Fill the outer side of the turn at the middle of three nodes
+///
+/// The inner side is already covered by the overlapping segments.
+fn push_join(triangles: &mut Vec<Triangle>, corner: &[Node], join: LineJoin) {
+    let (a, b, c) = (corner[0].position, corner[1], corner[2].position);
+    let incoming = direction(a, b.position);
+    let outgoing = direction(b.position, c);
+    let cross = incoming.0 * outgoing.1 - incoming.1 * outgoing.0;
+    let dot = incoming.0 * outgoing.0 + incoming.1 * outgoing.1;
+    let turn = cross.abs().atan2(dot);
+    if turn <= f32::EPSILON {
+        return;
+    }
+
+    // Normals pointing to the outer side of the turn
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let outer_in = scale(normal(a, b.position), side);
+    let outer_out = scale(normal(b.position, c), side);
+    let corner_in = offset(b.position, outer_in, b.half_width);
+    let corner_out = offset(b.position, outer_out, b.half_width);
+
+    match join {
+        LineJoin::Round => push_arc(
+            triangles,
+            b.position,
+            b.half_width,
+            angle(outer_in),
+            -side * turn,
+        ),
+        LineJoin::Miter => {
+            let bisector = direction((0.0, 0.0), add(outer_in, outer_out));
+            let cos_half_turn = bisector.0 * outer_in.0 + bisector.1 * outer_in.1;
+            if cos_half_turn * MITER_LIMIT > 1.0 {
+                let tip = offset(b.position, bisector, b.half_width / cos_half_turn);
+                triangles.push([b.position, corner_in, tip]);
+                triangles.push([b.position, tip, corner_out]);
+            } else {
+                triangles.push([b.position, corner_in, corner_out]);
+            }
+        }
+        LineJoin::Bevel => triangles.push([b.position, corner_in, corner_out]),
+    }
+}
+
+/// Fan of triangles covering a circle sector, angles in radians
+fn push_arc(
+    triangles: &mut Vec<Triangle>,
+    center: (f32, f32),
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+) {
+    // More segments for bigger circles, so the edge stays smooth
+    let circle_segments = (radius * 2.0).clamp(8.0, 64.0);
+    let segments = (sweep.abs() / (2.0 * PI) * circle_segments).ceil().max(1.0) as usize;
+
+    let point = |i: usize| {
+        let angle = start_angle + sweep * i as f32 / segments as f32;
+        offset(center, (angle.cos(), angle.sin()), radius)
+    };
+    for i in 0..segments {
+        triangles.push([center, point(i), point(i + 1)]);
+    }
+}
+
+fn add(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(v: (f32, f32), factor: f32) -> (f32, f32) {
+    (v.0 * factor, v.1 * factor)
+}
+
+fn length(v: (f32, f32)) -> f32 {
+    v.0.hypot(v.1)
+}
+
+fn angle(v: (f32, f32)) -> f32 {
+    v.1.atan2(v.0)
+}
+
+fn offset(point: (f32, f32), direction: (f32, f32), distance: f32) -> (f32, f32) {
+    add(point, scale(direction, distance))
+}
+
+/// Unit vector from `from` to `to`
+fn direction(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let v = sub(to, from);
+    scale(v, 1.0 / length(v))
+}
+
+/// Unit vector a quarter turn from the direction of a segment
+fn normal(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (x, y) = direction(from, to);
+    (-y, x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_WIDTH: f32 = 5.0;
+
+    fn style(line_join: LineJoin) -> LineStyle {
+        LineStyle {
+            width: HALF_WIDTH,
+            line_join,
+            ..LineStyle::default()
+        }
+    }
+
+    fn samples(points: &[(f32, f32)]) -> Vec<Sample> {
+        points
+            .iter()
+            .map(|&(x, y)| Sample {
+                x,
+                y,
+                pressure: 1.0,
+                time_ms: 0,
+            })
+            .collect()
+    }
+
+    /// Triangles in a half circle cap of the test half width
+    fn cap_triangles() -> usize {
+        let mut triangles = Vec::new();
+        push_arc(&mut triangles, (0.0, 0.0), HALF_WIDTH, 0.0, PI);
+        triangles.len()
+    }
+
+    /// Distance from a point to the furthest vertex closer than `within`
+    fn reach_around(triangles: &[Triangle], point: (f32, f32), within: f32) -> f32 {
+        triangles
+            .iter()
+            .flatten()
+            .map(|&vertex| length(sub(vertex, point)))
+            .filter(|&distance| distance < within)
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn single_sample_is_a_disc() {
+        let triangles = tessellate(&samples(&[(10.0, 10.0)]), &style(LineJoin::Round));
+
+        let mut covered_angle = 0.0;
+        for [center, a, b] in &triangles {
+            assert_eq!(*center, (10.0, 10.0));
+            assert!((length(sub(*a, *center)) - HALF_WIDTH).abs() < 1e-4);
+            assert!((length(sub(*b, *center)) - HALF_WIDTH).abs() < 1e-4);
+            let (da, db) = (sub(*a, *center), sub(*b, *center));
+            covered_angle += (da.0 * db.1 - da.1 * db.0).atan2(da.0 * db.0 + da.1 * db.1);
+        }
+        assert!((covered_angle - 2.0 * PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn repeated_samples_are_a_disc() {
+        let repeated = samples(&[(10.0, 10.0), (10.0, 10.0), (10.0, 10.0)]);
+        let single = samples(&[(10.0, 10.0)]);
+        assert_eq!(
+            tessellate(&repeated, &style(LineJoin::Round)),
+            tessellate(&single, &style(LineJoin::Round))
+        );
+    }
+
+    #[test]
+    fn straight_lines_have_no_joins() {
+        let line = samples(&[(0.0, 0.0), (100.0, 0.0)]);
+        let triangles = tessellate(&line, &style(LineJoin::Round));
+        assert_eq!(triangles.len(), 2 + 2 * cap_triangles());
+
+        let line = samples(&[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0)]);
+        for join in [LineJoin::Round, LineJoin::Miter, LineJoin::Bevel] {
+            let triangles = tessellate(&line, &style(join));
+            assert_eq!(triangles.len(), 4 + 2 * cap_triangles());
+        }
+    }
+
+    #[test]
+    fn both_ends_have_a_cap() {
+        let triangles = tessellate(
+            &samples(&[(0.0, 0.0), (100.0, 0.0)]),
+            &style(LineJoin::Round),
+        );
+        let xs = triangles.iter().flatten().map(|vertex| vertex.0);
+        let min_x = xs.clone().fold(f32::MAX, f32::min);
+        let max_x = xs.fold(f32::MIN, f32::max);
+        // Caps reach half a width past the ends, their vertices may miss the tip of the arc
+        let cap_depth = HALF_WIDTH * 0.9..=HALF_WIDTH + 1e-4;
+        assert!(cap_depth.contains(&-min_x));
+        assert!(cap_depth.contains(&(max_x - 100.0)));
+    }
+
+    #[test]
+    fn miters_past_the_limit_are_beveled() {
+        // Turns of 140 degrees are within the limit, 160 degrees are past it
+        let corner = |turn: f32| {
+            let outgoing = turn.to_radians();
+            samples(&[
+                (-100.0, 0.0),
+                (0.0, 0.0),
+                (100.0 * outgoing.cos(), 100.0 * outgoing.sin()),
+            ])
+        };
+        let miter_length = |turn: f32| HALF_WIDTH / ((PI - turn.to_radians()) / 2.0).sin();
+
+        let within = tessellate(&corner(140.0), &style(LineJoin::Miter));
+        let reach = reach_around(&within, (0.0, 0.0), 50.0);
+        assert!((reach - miter_length(140.0)).abs() < 1e-3);
+        assert!(reach < HALF_WIDTH * MITER_LIMIT);
+
+        let past = tessellate(&corner(160.0), &style(LineJoin::Miter));
+        let bevel = tessellate(&corner(160.0), &style(LineJoin::Bevel));
+        assert_eq!(past, bevel);
+        assert!((reach_around(&past, (0.0, 0.0), 50.0) - HALF_WIDTH).abs() < 1e-3);
+    }
+}